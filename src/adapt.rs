@@ -0,0 +1,150 @@
+use chrono::NaiveDateTime;
+
+use crate::config::AdaptiveSettings;
+use crate::schedule::{Interval, MAX_DAYS};
+use crate::state::{EventKind, PlantStatus};
+
+/// Number of most recent intervals considered, so that old habits are forgotten.
+pub const WINDOW: usize = 8;
+
+/// How a task's configured interval compares with how often it is actually done.
+#[derive(Clone, Debug)]
+pub struct Suggestion {
+    pub plant: String,
+    pub task: String,
+    /// The configured interval in days, or `None` if it is seasonal, in which case no
+    /// change is proposed.
+    pub current: Option<u64>,
+    /// The most recent intervals between the task being done, in days, oldest first.
+    pub intervals: Vec<f64>,
+    /// When the first of `intervals` began.
+    pub since: Option<NaiveDateTime>,
+    /// The median of `intervals`.
+    pub observed: Option<f64>,
+    /// The interval to use instead, if there is enough evidence for one.  May equal
+    /// `current`.
+    pub proposed: Option<u64>,
+}
+
+impl Suggestion {
+    /// The new interval, if it differs from the current one.
+    pub fn change(&self) -> Option<u64> {
+        self.proposed.filter(|p| Some(*p) != self.current)
+    }
+}
+
+pub fn median(values: &[f64]) -> Option<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let n = sorted.len();
+    match n {
+        0 => None,
+        _ if n % 2 == 1 => Some(sorted[n / 2]),
+        _ => Some((sorted[n / 2 - 1] + sorted[n / 2]) / 2.0),
+    }
+}
+
+/// Compare a task's interval with its history.  The proposal moves towards the observed
+/// interval by at most `settings.max_change` of the current one (and at least a day).
+pub fn suggest(
+    plant: &str,
+    task: &str,
+    interval: &Interval,
+    status: &PlantStatus,
+    settings: &AdaptiveSettings,
+) -> Suggestion {
+    let done: Vec<_> = status
+        .history_with_intervals()
+        .filter(|(e, _)| e.task == task && e.kind == EventKind::Done)
+        .filter_map(|(e, since)| Some((e.at, since?)))
+        .collect();
+    let recent = &done[done.len().saturating_sub(WINDOW)..];
+    let intervals: Vec<f64> = recent
+        .iter()
+        .map(|(_, d)| d.num_minutes() as f64 / (24.0 * 60.0))
+        .collect();
+    let since = recent.first().map(|(at, d)| *at - *d);
+    let observed = median(&intervals);
+    let current = match interval {
+        Interval::Days(days) => Some(*days),
+        Interval::Seasonal(_) => None,
+    };
+    let proposed = match (current, observed) {
+        (Some(current), Some(observed)) if intervals.len() >= settings.min_samples => {
+            let step = ((current as f64 * settings.max_change).round() as u64).max(1);
+            let target = (observed.round() as u64).max(1);
+            let lowest = current.saturating_sub(step).max(1);
+            Some(target.clamp(lowest, (current + step).min(MAX_DAYS)))
+        }
+        _ => None,
+    };
+    Suggestion {
+        plant: plant.to_string(),
+        task: task.to_string(),
+        current,
+        intervals,
+        since,
+        observed,
+        proposed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn watered_every(days: &[i64]) -> PlantStatus {
+        let mut status = PlantStatus::default();
+        let mut at: NaiveDateTime = "2023-04-01T08:00:00".parse().unwrap();
+        status.record(at, "water", EventKind::Done, None);
+        for d in days {
+            at += Duration::days(*d);
+            status.record(at, "water", EventKind::Done, None);
+            status.record(at, "mist", EventKind::Done, None);
+        }
+        status
+    }
+
+    #[test]
+    fn proposes_within_bounds() {
+        let settings = AdaptiveSettings::default();
+        let early = watered_every(&[5, 5, 6, 5]);
+        let s = suggest("calathea", "water", &Interval::Days(7), &early, &settings);
+        assert_eq!(s.intervals, [5.0, 5.0, 6.0, 5.0]);
+        assert_eq!(s.observed, Some(5.0));
+        assert_eq!(s.proposed, Some(5));
+        assert_eq!(s.change(), Some(5));
+
+        let late = watered_every(&[14, 15, 13]);
+        let s = suggest("snake plant", "water", &Interval::Days(7), &late, &settings);
+        assert_eq!(s.proposed, Some(9));
+
+        let s = suggest("fern", "water", &Interval::Days(5), &early, &settings);
+        assert_eq!(s.change(), None);
+    }
+
+    #[test]
+    fn needs_enough_history() {
+        let settings = AdaptiveSettings::default();
+        let s = suggest(
+            "fern",
+            "water",
+            &Interval::Days(7),
+            &watered_every(&[3, 3]),
+            &settings,
+        );
+        assert_eq!(s.observed, Some(3.0));
+        assert_eq!(s.proposed, None);
+        // Misting started a watering later, so has one interval fewer.
+        let s = suggest(
+            "fern",
+            "mist",
+            &Interval::Days(7),
+            &watered_every(&[3, 3, 3]),
+            &settings,
+        );
+        assert_eq!(s.intervals.len(), 2);
+        assert_eq!(s.proposed, None);
+    }
+}
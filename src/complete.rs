@@ -0,0 +1,284 @@
+use std::collections::BTreeSet;
+
+use clap::{Arg, Command};
+use clap_complete::Shell;
+
+use crate::config::Config;
+use crate::paths::PathArgs;
+use crate::profiles::Profiles;
+
+// The dynamic completion functions wrap the ones generated by clap_complete, whose names
+// vary between its versions, so they look up what the generated script registered.
+const BASH_DYNAMIC: &str = r#"
+_plant_paladin_static=$(complete -p plant_paladin)
+_plant_paladin_static=${_plant_paladin_static#* -F }
+_plant_paladin_static=${_plant_paladin_static%% *}
+complete -r plant_paladin
+_plant_paladin_dynamic() {
+    local candidates
+    mapfile -t candidates < <("${COMP_WORDS[0]}" complete-words -- "${COMP_WORDS[@]:0:COMP_CWORD+1}" 2>/dev/null)
+    if [[ ${#candidates[@]} -gt 0 ]]; then
+        COMPREPLY=()
+        local c
+        for c in "${candidates[@]}"; do
+            COMPREPLY+=("$(printf '%q' "$c")")
+        done
+        return 0
+    fi
+    "$_plant_paladin_static" "$@"
+}
+complete -F _plant_paladin_dynamic -o bashdefault -o default plant-paladin
+"#;
+
+const ZSH_DYNAMIC: &str = r#"
+_plant_paladin_static=${_comps[plant-paladin]:-_plant-paladin}
+_plant_paladin_dynamic() {
+    local -a candidates
+    candidates=("${(@f)$(${words[1]} complete-words -- "${(@)words[1,CURRENT]}" 2>/dev/null)}")
+    if [[ -n "${candidates[1]}" ]]; then
+        compadd -a candidates
+        return
+    fi
+    "$_plant_paladin_static" "$@"
+}
+compdef _plant_paladin_dynamic plant-paladin
+"#;
+
+const FISH_DYNAMIC: &str = r#"
+function __plant_paladin_dynamic
+    set -l words (commandline -opc)
+    $words[1] complete-words -- $words (commandline -ct) 2>/dev/null
+end
+complete -c plant-paladin -a '(__plant_paladin_dynamic)'
+"#;
+
+/// Print a completion script for `shell`.  For bash, zsh and fish, the script asks the
+/// hidden `complete-words` command for plant names, rooms, tags, tasks and profiles, falling
+/// back to clap's completion of subcommands and flags.
+pub fn print_script(shell: Shell, cmd: Command) {
+    print!("{}", script(shell, cmd));
+}
+
+fn script(shell: Shell, cmd: Command) -> String {
+    // clap_complete's bash script names its cases after the command but selects them
+    // after the binary, escaping a hyphen differently in each, so that nested subcommands
+    // never complete.  The bash script is generated for a name without one, and
+    // registered for the real name by `BASH_DYNAMIC`.
+    let mut cmd = match shell {
+        Shell::Bash => cmd.name("plant_paladin"),
+        _ => cmd.name("plant-paladin"),
+    };
+    let name = cmd.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut cmd, name, &mut script);
+    let mut script = String::from_utf8_lossy(&script).into_owned();
+    match shell {
+        Shell::Bash => script.push_str(BASH_DYNAMIC),
+        Shell::Zsh => script.push_str(ZSH_DYNAMIC),
+        Shell::Fish => script.push_str(FISH_DYNAMIC),
+        _ => {}
+    }
+    script
+}
+
+/// What the last of `words` (a command line, starting with the program name) should be
+/// completed with: the value name of the argument it is for, if it is a value rather than
+/// a subcommand or flag.  Also returns the path options given, so that completions come
+/// from the right config.
+pub fn position(mut cmd: Command, words: &[String]) -> (Option<String>, PathArgs) {
+    cmd.build();
+    let mut cmd = &cmd;
+    let mut path_args = PathArgs::default();
+    let Some((current, before)) = words.split_last() else {
+        return (None, path_args);
+    };
+    let mut record = |arg: &Arg, value: &str| match arg.get_id().as_str() {
+        "config" => path_args.config = Some(value.into()),
+        "state" => path_args.state = Some(value.into()),
+        "dir" => path_args.dir = Some(value.into()),
+        "profile" => path_args.profile = Some(value.into()),
+        _ => {}
+    };
+    let takes_value = |arg: &Arg| arg.get_action().takes_values();
+    // An option still waiting for its value.
+    let mut pending: Option<&Arg> = None;
+    let mut positionals = 0;
+    let mut only_positionals = false;
+    for word in before.iter().skip(1) {
+        if let Some(arg) = pending.take() {
+            record(arg, word);
+        } else if only_positionals || word == "-" || !word.starts_with('-') {
+            match cmd.find_subcommand(word).filter(|_| positionals == 0) {
+                Some(sub) => cmd = sub,
+                None => positionals += 1,
+            }
+        } else if word == "--" {
+            only_positionals = true;
+        } else if let Some(long) = word.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (long, None),
+            };
+            if let Some(arg) = cmd.get_arguments().find(|a| a.get_long() == Some(name)) {
+                match value {
+                    Some(value) => record(arg, value),
+                    None if takes_value(arg) => pending = Some(arg),
+                    None => {}
+                }
+            }
+        } else {
+            // Clustered short flags, the last of which may take a value: `-af`, `-n note`
+            // or `-nnote`.
+            let shorts = &word[1..];
+            for (i, c) in shorts.char_indices() {
+                let Some(arg) = cmd.get_arguments().find(|a| a.get_short() == Some(c)) else {
+                    continue;
+                };
+                if takes_value(arg) {
+                    match &shorts[i + c.len_utf8()..] {
+                        "" => pending = Some(arg),
+                        value => record(arg, value),
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    let arg = match pending {
+        Some(arg) => Some(arg),
+        None if current.starts_with('-') && !only_positionals => None,
+        None => {
+            let args: Vec<&Arg> = cmd.get_positionals().collect();
+            args.get(positionals).copied().or_else(|| {
+                args.last()
+                    .copied()
+                    .filter(|a| a.get_num_args().is_some_and(|n| n.max_values() > 1))
+            })
+        }
+    };
+    let value_name = arg
+        .and_then(Arg::get_value_names)
+        .and_then(|names| names.first())
+        .map(|name| name.to_string());
+    (value_name, path_args)
+}
+
+/// Values for an argument with the given value name which start with `prefix`, ignoring
+/// case.
+pub fn candidates(
+    value_name: &str,
+    prefix: &str,
+    config: Option<&Config>,
+    profiles: &Profiles,
+) -> Vec<String> {
+    let plants = config.into_iter().flat_map(|c| c.plants.iter());
+    let values: BTreeSet<String> = match value_name {
+        "PLANT" => plants.map(|(name, _)| name.clone()).collect(),
+        "TASK" => plants.flat_map(|(_, p)| p.tasks.keys().cloned()).collect(),
+        "ROOM" => plants.filter_map(|(_, p)| p.room.clone()).collect(),
+        "TAG" => plants.flat_map(|(_, p)| p.tags.iter().cloned()).collect(),
+        "PROFILE" => profiles.list().unwrap_or_default().into_iter().collect(),
+        _ => BTreeSet::new(),
+    };
+    let prefix = prefix.to_lowercase();
+    values
+        .into_iter()
+        .filter(|v| v.to_lowercase().starts_with(&prefix))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn value_name(line: &str) -> Option<String> {
+        let mut words: Vec<String> = line.split(' ').map(String::from).collect();
+        if line.ends_with(' ') {
+            words.pop();
+            words.push(String::new());
+        }
+        position(crate::Cli::command(), &words).0
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(value_name("plant-paladin wa"), None);
+        assert_eq!(value_name("plant-paladin water "), Some("PLANT".into()));
+        assert_eq!(
+            value_name("plant-paladin water fern fi"),
+            Some("PLANT".into())
+        );
+        assert_eq!(value_name("plant-paladin water --ro"), None);
+        assert_eq!(
+            value_name("plant-paladin water --room "),
+            Some("ROOM".into())
+        );
+        assert_eq!(value_name("plant-paladin do "), Some("TASK".into()));
+        assert_eq!(
+            value_name("plant-paladin do -n note mist "),
+            Some("PLANT".into())
+        );
+        assert_eq!(
+            value_name("plant-paladin --profile office nag --tag "),
+            Some("TAG".into())
+        );
+        assert_eq!(value_name("plant-paladin plant rm "), Some("PLANT".into()));
+        assert_eq!(value_name("plant-paladin plant add "), Some("NAME".into()));
+        assert_eq!(
+            value_name("plant-paladin snooze fern -t "),
+            Some("TASK".into())
+        );
+        assert_eq!(
+            value_name("plant-paladin --profile "),
+            Some("PROFILE".into())
+        );
+
+        let words: Vec<String> = ["plant-paladin", "--profile=office", "water", ""]
+            .map(String::from)
+            .into();
+        let (_, path_args) = position(crate::Cli::command(), &words);
+        assert_eq!(path_args.profile.as_deref(), Some("office"));
+    }
+
+    /// Complete the last word of `line` with the bash script, as bash would on tab.
+    fn complete_in_bash(line: &str) -> anyhow::Result<Vec<String>> {
+        let script = script(Shell::Bash, crate::Cli::command());
+        let test = format!(
+            r#"{script}
+COMP_WORDS=({line})
+COMP_CWORD=$((${{#COMP_WORDS[@]}} - 1))
+_plant_paladin_dynamic plant-paladin "${{COMP_WORDS[COMP_CWORD]}}" "${{COMP_WORDS[COMP_CWORD-1]}}"
+printf '%s\n' "${{COMPREPLY[@]}}"
+"#
+        );
+        let output = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(test)
+            .output()?;
+        anyhow::ensure!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bash_completes_subcommands() -> anyhow::Result<()> {
+        assert_eq!(complete_in_bash("plant-paladin fo")?, ["forecast"]);
+        let mut plant = complete_in_bash("plant-paladin plant re")?;
+        plant.sort();
+        assert_eq!(plant, ["remove", "rename"]);
+        assert_eq!(complete_in_bash("plant-paladin export i")?, ["ics"]);
+        assert_eq!(
+            complete_in_bash("plant-paladin suggest-intervals --ap")?,
+            ["--apply"]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::names::{lookup, or_list, Lookup};
+use crate::notify::NotifySettings;
+use crate::schedule::{Hemisphere, Interval};
+
+/// Name of the watering task, which is what `watering_interval` configures.
+pub const WATER: &str = "water";
+
+/// Name of the sensor metric compared against `moisture_threshold`.
+pub const MOISTURE: &str = "moisture";
+
+/// Top-level tables in the config file which are not plants.
+pub const RESERVED_NAMES: &[&str] = &["settings", "notify"];
+
+pub fn validate_plant_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        bail!("plant name must not be empty")
+    }
+    if RESERVED_NAMES.contains(&name) {
+        bail!("{name} is reserved and cannot be used as a plant name")
+    }
+    Ok(())
+}
+
+/// On-disk representation of a [`Plant`].  `watering_interval` is shorthand for a
+/// `water` entry in `tasks`.
+#[derive(Deserialize)]
+struct PlantRepr {
+    watering_interval: Option<Interval>,
+    #[serde(default)]
+    tasks: BTreeMap<String, Interval>,
+    room: Option<String>,
+    #[serde(default)]
+    tags: BTreeSet<String>,
+    moisture_threshold: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "PlantRepr")]
+pub struct Plant {
+    /// Care tasks and their intervals, keyed by task name.
+    pub tasks: BTreeMap<String, Interval>,
+    pub room: Option<String>,
+    pub tags: BTreeSet<String>,
+    /// Watering falls due whenever the latest moisture reading since the plant was last
+    /// watered is below this, however recently that was.
+    pub moisture_threshold: Option<f64>,
+}
+
+impl From<PlantRepr> for Plant {
+    fn from(repr: PlantRepr) -> Self {
+        let mut tasks = repr.tasks;
+        if let Some(interval) = repr.watering_interval {
+            tasks.insert(WATER.to_string(), interval);
+        }
+        Plant {
+            tasks,
+            room: repr.room.filter(|r| !r.trim().is_empty()),
+            tags: repr.tags,
+            moisture_threshold: repr.moisture_threshold,
+        }
+    }
+}
+
+impl Plant {
+    /// Whether the plant is in `room`, ignoring case.
+    pub fn in_room(&self, room: &str) -> bool {
+        self.room
+            .as_deref()
+            .is_some_and(|r| r.eq_ignore_ascii_case(room))
+    }
+
+    /// Whether the plant has `tag`, ignoring case.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Which hemisphere the plants live in, used to work out the seasons.
+    #[serde(default)]
+    pub hemisphere: Hemisphere,
+    #[serde(default)]
+    pub adaptive: AdaptiveSettings,
+    #[serde(default)]
+    pub daemon: DaemonSettings,
+}
+
+/// How intervals are adapted to the care actually recorded.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveSettings {
+    /// Whether to adjust intervals automatically whenever care is recorded.  Otherwise
+    /// `suggest-intervals` only suggests changes.
+    pub enabled: bool,
+    /// The largest change made at once, as a fraction of the current interval.  Changes
+    /// of one day are always allowed.
+    pub max_change: f64,
+    /// The number of recorded intervals needed before a change is suggested.
+    pub min_samples: usize,
+}
+
+impl Default for AdaptiveSettings {
+    fn default() -> Self {
+        AdaptiveSettings {
+            enabled: false,
+            max_change: 0.25,
+            min_samples: 3,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DaemonSettingsRepr {
+    times: Vec<String>,
+}
+
+/// When `daemon` checks for due plants.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "DaemonSettingsRepr")]
+pub struct DaemonSettings {
+    /// Times of day, sorted.
+    pub times: Vec<NaiveTime>,
+}
+
+impl Default for DaemonSettings {
+    fn default() -> Self {
+        DaemonSettings {
+            times: vec![NaiveTime::from_hms_opt(8, 0, 0).unwrap()],
+        }
+    }
+}
+
+impl TryFrom<DaemonSettingsRepr> for DaemonSettings {
+    type Error = String;
+
+    fn try_from(repr: DaemonSettingsRepr) -> Result<Self, Self::Error> {
+        let mut times = Vec::new();
+        for time in &repr.times {
+            let time = NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| format!("invalid time {time:?}, expected HH:MM"))?;
+            times.push(time);
+        }
+        if times.is_empty() {
+            return Err("at least one time is needed".to_string());
+        }
+        times.sort();
+        times.dedup();
+        Ok(DaemonSettings { times })
+    }
+}
+
+#[derive(Deserialize)]
+struct ConfigRepr {
+    #[serde(default)]
+    settings: Settings,
+    #[serde(default)]
+    notify: NotifySettings,
+    #[serde(flatten)]
+    plants: HashMap<String, Plant>,
+}
+
+/// Plant names are matched ignoring case, so they must be unique ignoring case.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "ConfigRepr")]
+pub struct Config {
+    pub settings: Settings,
+    pub notify: NotifySettings,
+    #[serde(flatten)]
+    pub plants: HashMap<String, Plant>,
+}
+
+impl TryFrom<ConfigRepr> for Config {
+    type Error = String;
+
+    fn try_from(repr: ConfigRepr) -> Result<Self, Self::Error> {
+        let mut names: Vec<&String> = repr.plants.keys().collect();
+        names.sort_by_key(|n| n.to_lowercase());
+        for pair in names.windows(2) {
+            if pair[0].to_lowercase() == pair[1].to_lowercase() {
+                return Err(format!(
+                    "plants {:?} and {:?} differ only in case",
+                    pair[0], pair[1]
+                ));
+            }
+        }
+        Ok(Config {
+            settings: repr.settings,
+            notify: repr.notify,
+            plants: repr.plants,
+        })
+    }
+}
+
+impl Config {
+    /// The config's spelling of a plant name, ignoring case.
+    pub fn find_plant(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.plants
+            .keys()
+            .find(|k| k.to_lowercase() == name)
+            .map(String::as_str)
+    }
+
+    /// Resolve a plant name given on the command line, ignoring case and accepting an
+    /// unambiguous prefix.  The error suggests similar names.
+    pub fn resolve_plant(&self, name: &str) -> Result<&str> {
+        match lookup(name, self.plants.keys().map(String::as_str)) {
+            Lookup::Found(plant) => Ok(plant),
+            Lookup::Ambiguous(plants) => Err(anyhow!(
+                "{name} is ambiguous, it could be {}",
+                or_list(&plants)
+            )),
+            Lookup::Missing(close) if close.is_empty() => {
+                Err(anyhow!("no plant named {name} in config"))
+            }
+            Lookup::Missing(close) => Err(anyhow!(
+                "no plant named {name} in config, did you mean {}?",
+                or_list(&close)
+            )),
+        }
+    }
+
+    /// The interval in days in effect on `date` for a plant's task, if the plant has that task.
+    pub fn interval(&self, plant: &Plant, task: &str, date: NaiveDate) -> Option<u64> {
+        plant
+            .tasks
+            .get(task)
+            .map(|i| i.days_on(date, self.settings.hemisphere))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watering_interval_is_water_task() -> anyhow::Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            [fern]
+            watering_interval = 7
+            tasks = { mist = 2 }
+
+            [cactus.tasks]
+            water = 21
+            rotate = 14
+            "#,
+        )?;
+        let today = chrono::Local::now().date_naive();
+        let interval =
+            |plant: &str, task: &str| config.interval(&config.plants[plant], task, today);
+        assert_eq!(interval("fern", WATER), Some(7));
+        assert_eq!(interval("fern", "mist"), Some(2));
+        assert_eq!(interval("cactus", WATER), Some(21));
+        assert_eq!(interval("cactus", "fertilize"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn rooms_and_tags() -> anyhow::Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            [fern]
+            watering_interval = 7
+            room = "Living Room"
+            tags = ["ferns", "humid"]
+
+            [cactus]
+            watering_interval = 21
+            "#,
+        )?;
+        let fern = &config.plants["fern"];
+        assert!(fern.in_room("living room"));
+        assert!(fern.has_tag("Humid"));
+        assert!(!fern.has_tag("succulents"));
+        assert_eq!(config.plants["cactus"].room, None);
+        assert!(config.plants["cactus"].tags.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn daemon_times() -> anyhow::Result<()> {
+        let config: Config = toml::from_str("[fern]\nwatering_interval = 7")?;
+        assert_eq!(
+            config.settings.daemon.times,
+            [NaiveTime::from_hms_opt(8, 0, 0).unwrap()]
+        );
+        let config: Config = toml::from_str(
+            r#"
+            [settings.daemon]
+            times = ["18:00", "08:30"]
+            "#,
+        )?;
+        assert_eq!(
+            config.settings.daemon.times,
+            [
+                NaiveTime::from_hms_opt(8, 30, 0).unwrap(),
+                NaiveTime::from_hms_opt(18, 0, 0).unwrap()
+            ]
+        );
+        assert!(toml::from_str::<Config>("[settings.daemon]\ntimes = [\"8am\"]").is_err());
+        assert!(toml::from_str::<Config>("[settings.daemon]\ntimes = []").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn names_ignore_case() -> anyhow::Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            ["Boston Fern"]
+            watering_interval = 7
+            [fig]
+            watering_interval = 10
+            "#,
+        )?;
+        assert_eq!(config.find_plant("boston fern"), Some("Boston Fern"));
+        assert_eq!(config.resolve_plant("BOS")?, "Boston Fern");
+        let err = config.resolve_plant("fgi").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no plant named fgi in config, did you mean fig?"
+        );
+
+        let duplicate = toml::from_str::<Config>(
+            r#"
+            [fern]
+            watering_interval = 7
+            [Fern]
+            watering_interval = 7
+            "#,
+        );
+        assert!(duplicate.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn notify_section() -> anyhow::Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            [notify]
+            desktop = true
+            webhook = { url = "https://example.com/hook" }
+
+            [notify.email]
+            server = "smtp.example.com"
+            from = "paladin@example.com"
+            to = ["me@example.com"]
+
+            [fern]
+            watering_interval = 7
+            "#,
+        )?;
+        assert!(config.notify.desktop);
+        let email = config.notify.email.as_ref().unwrap();
+        assert_eq!(email.security, crate::notify::Security::Starttls);
+        assert!(config.notify.command.is_none());
+        assert_eq!(config.plants.keys().collect::<Vec<_>>(), ["fern"]);
+        assert!(validate_plant_name("notify").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn seasonal_intervals_follow_hemisphere() -> anyhow::Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            [settings]
+            hemisphere = "south"
+
+            [fern]
+            watering_interval = { summer = 4, winter = 10, default = 7 }
+            "#,
+        )?;
+        assert_eq!(config.settings.hemisphere, Hemisphere::South);
+        assert!(!config.plants.contains_key("settings"));
+        let fern = &config.plants["fern"];
+        let july = "2023-07-15".parse().unwrap();
+        let january = "2023-01-15".parse().unwrap();
+        assert_eq!(config.interval(fern, WATER, july), Some(10));
+        assert_eq!(config.interval(fern, WATER, january), Some(4));
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+use anyhow::{anyhow, Context, Result};
+use toml_edit::{Array, Document, Item, Key, Table, TableLike, Value};
+
+use crate::config::{Config, WATER};
+
+/// A config file open for editing.  Edits go through `toml_edit` so that comments, key
+/// order and whitespace in the rest of the file are preserved.
+pub struct ConfigDocument {
+    doc: Document,
+}
+
+/// Replace a value, keeping any comments and whitespace around the old one.
+fn set_value(item: &mut Item, value: impl Into<Value>) {
+    let mut value = value.into();
+    if let Some(old) = item.as_value() {
+        *value.decor_mut() = old.decor().clone();
+    }
+    *item = Item::Value(value);
+}
+
+fn highest_position(table: &Table) -> usize {
+    table
+        .iter()
+        .filter_map(|(_, item)| item.as_table())
+        .map(|t| t.position().unwrap_or(0).max(highest_position(t)))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Whether a tag in the document is `tag`, which like all tags is matched ignoring case.
+fn same_tag(value: &Value, tag: &str) -> bool {
+    value.as_str().is_some_and(|t| t.eq_ignore_ascii_case(tag))
+}
+
+impl ConfigDocument {
+    pub fn parse(contents: &str) -> Result<Self> {
+        let doc = contents.parse().context("failed to parse config")?;
+        Ok(ConfigDocument { doc })
+    }
+
+    /// Deserialise the edited document, checking it is still a valid config.
+    pub fn config(&self) -> Result<Config> {
+        toml::from_str(&self.to_string()).context("edited config is invalid")
+    }
+
+    fn plant_mut(&mut self, name: &str) -> Result<&mut dyn TableLike> {
+        self.doc
+            .get_mut(name)
+            .and_then(Item::as_table_like_mut)
+            .ok_or_else(|| anyhow!("no plant named {name} in config"))
+    }
+
+    pub fn add_plant(&mut self, name: &str, tasks: &[(String, u64)]) {
+        let mut plant = Table::new();
+        plant.set_position(highest_position(self.doc.as_table()) + 1);
+        let mut other_tasks = Table::new();
+        for (task, days) in tasks {
+            if task == WATER {
+                plant.insert("watering_interval", toml_edit::value(*days as i64));
+            } else {
+                other_tasks.insert(task, toml_edit::value(*days as i64));
+            }
+        }
+        if !other_tasks.is_empty() {
+            plant.insert("tasks", Item::Table(other_tasks));
+        }
+        self.doc.insert(name, Item::Table(plant));
+    }
+
+    pub fn remove_plant(&mut self, name: &str) -> Result<()> {
+        self.doc
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("no plant named {name} in config"))
+    }
+
+    /// Rename a plant in place, keeping its position in the file.
+    pub fn rename_plant(&mut self, old: &str, new: &str) -> Result<()> {
+        let root = self.doc.as_table_mut();
+        if !root.contains_key(old) {
+            return Err(anyhow!("no plant named {old} in config"));
+        }
+        let keys: Vec<String> = root.iter().map(|(k, _)| k.to_string()).collect();
+        for k in keys {
+            let (key, item) = root.remove_entry(&k).unwrap();
+            let key = if k == old {
+                Key::new(new).with_decor(key.decor().clone())
+            } else {
+                key
+            };
+            root.insert_formatted(&key, item);
+        }
+        Ok(())
+    }
+
+    /// Set a task's interval.  The watering interval is written as `watering_interval`
+    /// unless the plant already lists it under `tasks`.
+    pub fn set_task(&mut self, name: &str, task: &str, days: u64) -> Result<()> {
+        let plant = self.plant_mut(name)?;
+        let days = days as i64;
+        let in_tasks = plant
+            .get("tasks")
+            .and_then(Item::as_table_like)
+            .is_some_and(|t| t.contains_key(task));
+        if task == WATER && !in_tasks {
+            let item = plant
+                .entry("watering_interval")
+                .or_insert(toml_edit::value(days));
+            set_value(item, days);
+            return Ok(());
+        }
+        let tasks = plant
+            .entry("tasks")
+            .or_insert(Item::Table(Table::new()))
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("`tasks` of plant {name} is not a table"))?;
+        let item = tasks.entry(task).or_insert(toml_edit::value(days));
+        set_value(item, days);
+        Ok(())
+    }
+
+    /// Set the plant's room, or remove it if `room` is `None`.
+    pub fn set_room(&mut self, name: &str, room: Option<&str>) -> Result<()> {
+        let plant = self.plant_mut(name)?;
+        match room {
+            Some(room) => {
+                let item = plant.entry("room").or_insert(toml_edit::value(room));
+                set_value(item, room);
+            }
+            None => {
+                plant.remove("room");
+            }
+        }
+        Ok(())
+    }
+
+    fn tags_mut(&mut self, name: &str) -> Result<&mut Array> {
+        self.plant_mut(name)?
+            .entry("tags")
+            .or_insert(toml_edit::value(Array::new()))
+            .as_array_mut()
+            .ok_or_else(|| anyhow!("`tags` of plant {name} is not an array"))
+    }
+
+    /// Add a tag, unless the plant has it already in some case.
+    pub fn add_tag(&mut self, name: &str, tag: &str) -> Result<()> {
+        let tags = self.tags_mut(name)?;
+        if !tags.iter().any(|t| same_tag(t, tag)) {
+            tags.push(tag);
+        }
+        Ok(())
+    }
+
+    /// Remove a tag, in whatever case, returning whether the plant had it.
+    pub fn remove_tag(&mut self, name: &str, tag: &str) -> Result<bool> {
+        let tags = self.tags_mut(name)?;
+        let len = tags.len();
+        for i in (0..len).rev() {
+            if same_tag(tags.get(i).unwrap(), tag) {
+                tags.remove(i);
+            }
+        }
+        tags.fmt();
+        let removed = tags.len() != len;
+        if tags.is_empty() {
+            self.plant_mut(name)?.remove("tags");
+        }
+        Ok(removed)
+    }
+
+    /// Remove a task, returning whether the plant had it.
+    pub fn remove_task(&mut self, name: &str, task: &str) -> Result<bool> {
+        let plant = self.plant_mut(name)?;
+        let mut removed = false;
+        if task == WATER {
+            removed |= plant.remove("watering_interval").is_some();
+        }
+        if let Some(tasks) = plant.get_mut("tasks").and_then(Item::as_table_like_mut) {
+            removed |= tasks.remove(task).is_some();
+            if tasks.is_empty() {
+                plant.remove("tasks");
+            }
+        }
+        Ok(removed)
+    }
+}
+
+impl std::fmt::Display for ConfigDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.doc.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMENTED: &str = r#"# Plants at home
+
+[settings]
+hemisphere = "south"
+
+# on the bookshelf, from Grandma
+[fern]
+watering_interval = 7 # in days
+
+[fern.tasks]
+mist = 2 # only in summer really
+
+# by the front door
+[cactus]
+watering_interval = 21
+"#;
+
+    #[test]
+    fn add_appends_and_keeps_comments() -> Result<()> {
+        let mut doc = ConfigDocument::parse(COMMENTED)?;
+        doc.add_plant(
+            "monstera",
+            &[(WATER.to_string(), 10), ("fertilize".to_string(), 30)],
+        );
+        let expected = format!(
+            "{COMMENTED}
+[monstera]
+watering_interval = 10
+
+[monstera.tasks]
+fertilize = 30
+"
+        );
+        assert_eq!(doc.to_string(), expected);
+        assert_eq!(doc.config()?.plants.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn rename_keeps_position_and_comments() -> Result<()> {
+        let mut doc = ConfigDocument::parse(COMMENTED)?;
+        doc.rename_plant("fern", "boston fern")?;
+        assert_eq!(
+            doc.to_string(),
+            COMMENTED
+                .replace("[fern]", "[\"boston fern\"]")
+                .replace("[fern.tasks]", "[\"boston fern\".tasks]")
+        );
+        assert!(doc.rename_plant("fern", "x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn remove_takes_only_that_plant() -> Result<()> {
+        let mut doc = ConfigDocument::parse(COMMENTED)?;
+        doc.remove_plant("cactus")?;
+        assert_eq!(
+            doc.to_string(),
+            COMMENTED.replace(
+                "\n# by the front door\n[cactus]\nwatering_interval = 21\n",
+                ""
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn set_and_remove_tasks_keep_comments() -> Result<()> {
+        let mut doc = ConfigDocument::parse(COMMENTED)?;
+        doc.set_task("fern", WATER, 5)?;
+        doc.set_task("fern", "mist", 3)?;
+        doc.set_task("cactus", "rotate", 14)?;
+        assert!(doc.remove_task("fern", "mist")?);
+        assert!(!doc.remove_task("fern", "prune")?);
+        let expected = COMMENTED
+            .replace(
+                "watering_interval = 7 # in days",
+                "watering_interval = 5 # in days",
+            )
+            .replace("\n[fern.tasks]\nmist = 2 # only in summer really\n", "")
+            + "\n[cactus.tasks]\nrotate = 14\n";
+        assert_eq!(doc.to_string(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn rooms_and_tags() -> Result<()> {
+        let mut doc = ConfigDocument::parse(COMMENTED)?;
+        doc.set_room("fern", Some("living room"))?;
+        doc.add_tag("fern", "ferns")?;
+        doc.add_tag("fern", "humid")?;
+        doc.add_tag("fern", "Ferns")?;
+        doc.add_tag("fern", "tropical")?;
+        doc.add_tag("cactus", "succulents")?;
+        assert!(doc.remove_tag("cactus", "Succulents")?);
+        assert!(doc.remove_tag("fern", "FERNS")?);
+        doc.add_tag("fern", "ferns")?;
+        assert!(!doc.remove_tag("cactus", "succulents")?);
+        let expected = COMMENTED.replace(
+            "watering_interval = 7 # in days\n",
+            "watering_interval = 7 # in days\nroom = \"living room\"\ntags = [\"humid\", \"tropical\", \"ferns\"]\n",
+        );
+        assert_eq!(doc.to_string(), expected);
+        let config = doc.config()?;
+        assert!(config.plants["fern"].in_room("Living Room"));
+
+        doc.set_room("fern", None)?;
+        assert!(!doc.to_string().contains("room ="));
+        Ok(())
+    }
+}
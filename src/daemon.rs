@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+/// The first of `times`, on any day, strictly after `now`.  `times` must be sorted and
+/// not empty.
+pub fn next_run(now: NaiveDateTime, times: &[NaiveTime]) -> NaiveDateTime {
+    match times.iter().find(|t| **t > now.time()) {
+        Some(t) => now.date().and_time(*t),
+        None => (now.date() + Duration::days(1)).and_time(times[0]),
+    }
+}
+
+/// A flag which is set once SIGTERM or SIGINT is received, so that the daemon can finish
+/// what it is doing and exit.
+pub fn shutdown_flag() -> Result<Arc<AtomicBool>> {
+    let flag = Arc::new(AtomicBool::new(false));
+    for signal in [SIGTERM, SIGINT] {
+        signal_hook::flag::register(signal, Arc::clone(&flag))
+            .context("failed to install signal handler")?;
+    }
+    Ok(flag)
+}
+
+/// Notices when a file is modified, created or removed.
+pub struct Watched {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl Watched {
+    pub fn new(path: &Path) -> Self {
+        Watched {
+            path: path.to_path_buf(),
+            modified: modified(path),
+        }
+    }
+
+    /// Whether the file has changed since this was last called.
+    pub fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Log a line to stderr, with the time.
+pub fn log(message: &str) {
+    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+    eprintln!("{now} {message}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_runs() {
+        let times: Vec<NaiveTime> = ["08:00:00", "18:00:00"]
+            .iter()
+            .map(|t| t.parse().unwrap())
+            .collect();
+        let at = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        assert_eq!(
+            next_run(at("2023-04-01T07:00:00"), &times),
+            at("2023-04-01T08:00:00")
+        );
+        assert_eq!(
+            next_run(at("2023-04-01T08:00:00"), &times),
+            at("2023-04-01T18:00:00")
+        );
+        assert_eq!(
+            next_run(at("2023-04-01T19:30:00"), &times),
+            at("2023-04-02T08:00:00")
+        );
+    }
+}
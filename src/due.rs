@@ -0,0 +1,233 @@
+use chrono::{Days, Duration, NaiveDate, NaiveDateTime};
+
+use crate::config::{Config, MOISTURE, WATER};
+use crate::state::State;
+
+/// Where a single care task for a single plant stands at a point in time.
+#[derive(Clone, Debug)]
+pub struct TaskStatus {
+    pub plant: String,
+    pub room: Option<String>,
+    pub task: String,
+    /// Interval in days in effect at the time of evaluation.
+    pub interval: u64,
+    pub last_done: Option<NaiveDateTime>,
+    /// End of the snooze in effect at the time of evaluation.
+    pub snoozed_until: Option<NaiveDateTime>,
+    /// For watering, the latest moisture reading taken since the plant was last watered.
+    pub moisture: Option<f64>,
+    pub moisture_threshold: Option<f64>,
+}
+
+impl TaskStatus {
+    /// When the task next falls due, or `None` if it has never been done (and so is due
+    /// immediately).
+    pub fn next_due(&self) -> Option<NaiveDateTime> {
+        self.last_done
+            .map(|t| t + Duration::days(self.interval as i64))
+    }
+
+    /// Whether the latest moisture reading is below the plant's threshold.
+    pub fn is_dry(&self) -> bool {
+        match (self.moisture, self.moisture_threshold) {
+            (Some(moisture), Some(threshold)) => moisture < threshold,
+            _ => false,
+        }
+    }
+
+    /// Whether the task is due and not snoozed.  A dry plant is due for watering however
+    /// recently it was watered.
+    pub fn is_due(&self, now: NaiveDateTime) -> bool {
+        if self.snoozed_until.is_some_and(|t| t > now) {
+            return false;
+        }
+        if self.is_dry() {
+            return true;
+        }
+        match self.next_due() {
+            Some(t) => t <= now,
+            None => true,
+        }
+    }
+
+    /// Whole days since the task was last done.
+    pub fn days_since(&self, now: NaiveDateTime) -> Option<i64> {
+        self.last_done.map(|t| (now - t).num_days())
+    }
+
+    /// Calendar days until the task is due; negative if overdue.
+    pub fn days_remaining(&self, today: NaiveDate) -> Option<i64> {
+        self.next_due().map(|t| (t.date() - today).num_days())
+    }
+}
+
+/// Evaluate every task of every plant at `now`, most urgent first.  Plants missing from
+/// `state` are treated as never having been cared for.
+pub fn task_statuses(config: &Config, state: &State, now: NaiveDateTime) -> Vec<TaskStatus> {
+    let mut statuses = Vec::new();
+    for (name, plant) in &config.plants {
+        let status = state.plants.get(name);
+        for task in plant.tasks.keys() {
+            let last_done = status.and_then(|s| s.last_done(task));
+            let moisture = status
+                .filter(|_| task == WATER)
+                .and_then(|s| s.latest_reading(MOISTURE))
+                .filter(|r| last_done.is_none_or(|t| r.at > t))
+                .map(|r| r.value);
+            statuses.push(TaskStatus {
+                plant: name.clone(),
+                room: plant.room.clone(),
+                task: task.clone(),
+                interval: config.interval(plant, task, now.date()).unwrap(),
+                last_done,
+                snoozed_until: status.and_then(|s| s.snoozed_until(task, now)),
+                moisture,
+                moisture_threshold: plant.moisture_threshold,
+            });
+        }
+    }
+    statuses
+        .sort_by(|a, b| (a.next_due(), &a.plant, &a.task).cmp(&(b.next_due(), &b.plant, &b.task)));
+    statuses
+}
+
+/// The dates a task falls due from now on, assuming it is done on each of them.  The
+/// first is today if the task is due now, or the day its snooze ends if it is snoozed.  The
+/// rest follow at the interval in effect on each date, so seasonal changes are accounted for.
+pub fn upcoming<'a>(
+    config: &'a Config,
+    ts: &'a TaskStatus,
+    now: NaiveDateTime,
+) -> impl Iterator<Item = NaiveDate> + 'a {
+    let today = now.date();
+    let plant = &config.plants[&ts.plant];
+    let mut date = match ts.next_due() {
+        Some(t) if !ts.is_dry() => t.date().max(today),
+        _ => today,
+    };
+    if let Some(until) = ts.snoozed_until {
+        date = date.max(until.date());
+    }
+    std::iter::successors(Some(date), move |date| {
+        let days = config.interval(plant, &ts.task, *date).unwrap();
+        date.checked_add_days(Days::new(days))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{EventKind, Reading};
+
+    #[test]
+    fn most_urgent_first() -> anyhow::Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            [fern]
+            watering_interval = 7
+            [cactus]
+            watering_interval = 21
+            [orchid]
+            watering_interval = 10
+            "#,
+        )?;
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        let mut state = State::default();
+        for (plant, at) in [
+            ("fern", "2023-04-01T08:00:00"),
+            ("cactus", "2023-03-01T08:00:00"),
+        ] {
+            state.plants.entry(plant.to_string()).or_default().record(
+                t(at),
+                "water",
+                EventKind::Done,
+                None,
+            );
+        }
+        let now = t("2023-04-05T12:00:00");
+        let statuses = task_statuses(&config, &state, now);
+        let order: Vec<_> = statuses.iter().map(|s| s.plant.as_str()).collect();
+        assert_eq!(order, ["orchid", "cactus", "fern"]);
+        assert!(statuses[0].is_due(now));
+        assert_eq!(statuses[1].days_remaining(now.date()), Some(-14));
+        assert_eq!(statuses[2].days_remaining(now.date()), Some(3));
+        assert!(!statuses[2].is_due(now));
+        Ok(())
+    }
+
+    #[test]
+    fn upcoming_dates() -> anyhow::Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            [fern]
+            watering_interval = { summer = 3, default = 7 }
+            "#,
+        )?;
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        let d = |s: &str| s.parse::<NaiveDate>().unwrap();
+        let mut state = State::default();
+        let now = t("2023-05-20T12:00:00");
+        let water = |state: &State| task_statuses(&config, state, now).remove(0);
+        // Never watered, so due today; summer starts in June.
+        assert_eq!(
+            upcoming(&config, &water(&state), now)
+                .take(4)
+                .collect::<Vec<_>>(),
+            [
+                d("2023-05-20"),
+                d("2023-05-27"),
+                d("2023-06-03"),
+                d("2023-06-06")
+            ]
+        );
+
+        let fern = state.plants.entry("fern".to_string()).or_default();
+        fern.record(t("2023-05-15T08:00:00"), WATER, EventKind::Done, None);
+        let next = |state: &State| upcoming(&config, &water(state), now).next();
+        assert_eq!(next(&state), Some(d("2023-05-22")));
+        let fern = state.plants.get_mut("fern").unwrap();
+        fern.snooze(
+            t("2023-05-20T08:00:00"),
+            WATER,
+            t("2023-05-25T08:00:00"),
+            None,
+        );
+        assert_eq!(next(&state), Some(d("2023-05-25")));
+        Ok(())
+    }
+
+    #[test]
+    fn dry_plants_are_due() -> anyhow::Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            [fern]
+            watering_interval = 7
+            moisture_threshold = 25
+            "#,
+        )?;
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        let mut state = State::default();
+        let fern = state.plants.entry("fern".to_string()).or_default();
+        fern.record(t("2023-04-01T08:00:00"), WATER, EventKind::Done, None);
+        let reading = |at: &str, value| Reading {
+            at: t(at),
+            metric: MOISTURE.to_string(),
+            value,
+        };
+        fern.add_reading(reading("2023-03-31T20:00:00", 10.0));
+        let now = t("2023-04-03T12:00:00");
+        let water = |state: &State| task_statuses(&config, state, now).remove(0);
+        // Readings from before the last watering don't count.
+        assert_eq!(water(&state).moisture, None);
+        assert!(!water(&state).is_due(now));
+
+        let fern = state.plants.get_mut("fern").unwrap();
+        fern.add_reading(reading("2023-04-03T08:00:00", 30.0));
+        assert!(!water(&state).is_due(now));
+        let fern = state.plants.get_mut("fern").unwrap();
+        fern.add_reading(reading("2023-04-03T10:00:00", 20.0));
+        assert!(water(&state).is_dry());
+        assert!(water(&state).is_due(now));
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::Result;
+use fs2::FileExt;
+use posix_cli_utils::IoContext;
+
+/// Replace the contents of `path` atomically: the data is written and synced to a
+/// temporary file in the same directory, which is then renamed over `path`.  Readers
+/// see either the old or the new contents, never a partial write.  An existing file's
+/// permissions are kept, and if `path` is a symlink, the file it points to is replaced.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let target;
+    let path = if path.is_symlink() {
+        target = std::fs::canonicalize(path).context_read(path)?;
+        &target
+    } else {
+        path
+    };
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    let result = (|| {
+        let mut file = File::create(&tmp).context_write(&tmp)?;
+        // Before writing, so contents meant to be private are never readable by others.
+        if let Ok(metadata) = std::fs::metadata(path) {
+            file.set_permissions(metadata.permissions())
+                .context_write(&tmp)?;
+        }
+        file.write_all(contents).context_write(&tmp)?;
+        file.sync_all().context_write(&tmp)?;
+        std::fs::rename(&tmp, path).context_write(path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result?;
+    // Make the rename itself durable.
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        // A bare file name is in the current directory.
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        File::open(dir)
+            .and_then(|d| d.sync_all())
+            .context_write(dir)?;
+    }
+    Ok(())
+}
+
+/// An exclusive advisory lock, held until dropped.
+pub struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// Block until the lock on `path` (created if necessary) can be taken.
+    pub fn acquire(path: &Path) -> Result<Lock> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .context_write(path)?;
+        if file.try_lock_exclusive().is_err() {
+            eprintln!("waiting for another plant-paladin process to finish...");
+            file.lock_exclusive().context_write(path)?;
+        }
+        Ok(Lock { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_atomic_replaces_contents() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("plant-paladin-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("state.toml");
+        write_atomic(&path, b"old")?;
+        write_atomic(&path, b"new")?;
+        assert_eq!(std::fs::read_to_string(&path)?, "new");
+        let leftovers: Vec<_> = std::fs::read_dir(&dir)?.collect();
+        assert_eq!(leftovers.len(), 1);
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn write_atomic_accepts_bare_file_names() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("plant-paladin-bare-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let cwd = std::env::current_dir()?;
+        std::env::set_current_dir(&dir)?;
+        let result = write_atomic(Path::new("state.toml"), b"new");
+        std::env::set_current_dir(cwd)?;
+        result?;
+        assert_eq!(std::fs::read_to_string(dir.join("state.toml"))?, "new");
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_follows_symlinks() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("plant-paladin-link-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let (file, link) = (dir.join("dotfiles-config.toml"), dir.join("config.toml"));
+        std::fs::write(&file, "old")?;
+        std::os::unix::fs::symlink(&file, &link)?;
+        write_atomic(&link, b"new")?;
+        assert!(link.is_symlink());
+        assert_eq!(std::fs::read_to_string(&file)?, "new");
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_keeps_permissions() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("plant-paladin-perms-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("config.toml");
+        write_atomic(&path, b"old")?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        write_atomic(&path, b"new")?;
+        let mode = std::fs::metadata(&path)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+/// Lines longer than this many bytes are folded, as RFC 5545 requires.
+const LINE_LIMIT: usize = 75;
+
+/// An all-day calendar event.
+#[derive(Clone, Debug)]
+pub struct Event {
+    /// Identifies the event across exports, so that calendars update it rather than adding
+    /// a copy.
+    pub uid: String,
+    pub date: NaiveDate,
+    pub summary: String,
+    pub description: String,
+    pub location: Option<String>,
+}
+
+/// A UID made of `parts`, which are lowercased and percent-encoded so that any text can be
+/// used.
+pub fn uid(parts: &[&str]) -> String {
+    let parts: Vec<String> = parts
+        .iter()
+        .map(|part| {
+            let mut encoded = String::new();
+            for b in part.to_lowercase().bytes() {
+                match b {
+                    b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => encoded.push(b as char),
+                    _ => encoded.push_str(&format!("%{b:02X}")),
+                }
+            }
+            encoded
+        })
+        .collect();
+    format!("{}@plant-paladin", parts.join("."))
+}
+
+/// Escape a TEXT value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Append a content line, folding it so that no line exceeds [`LINE_LIMIT`] bytes
+/// without splitting a character.
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// An iCalendar file holding `events`.  `stamp` is when it was made.
+pub fn calendar(name: &str, events: &[Event], stamp: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    let date = |d: NaiveDate| d.format("%Y%m%d").to_string();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//plant-paladin//plant-paladin//EN");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, &format!("X-WR-CALNAME:{}", escape(name)));
+    for event in events {
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{}", event.uid));
+        push_line(
+            &mut out,
+            &format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")),
+        );
+        push_line(
+            &mut out,
+            &format!("DTSTART;VALUE=DATE:{}", date(event.date)),
+        );
+        push_line(
+            &mut out,
+            &format!("DTEND;VALUE=DATE:{}", date(event.date + Duration::days(1))),
+        );
+        push_line(&mut out, &format!("SUMMARY:{}", escape(&event.summary)));
+        push_line(
+            &mut out,
+            &format!("DESCRIPTION:{}", escape(&event.description)),
+        );
+        if let Some(location) = &event.location {
+            push_line(&mut out, &format!("LOCATION:{}", escape(location)));
+        }
+        push_line(&mut out, "TRANSP:TRANSPARENT");
+        push_line(&mut out, "END:VEVENT");
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_calendar() {
+        let event = Event {
+            uid: uid(&["default", "Boston Fern", "water", "1"]),
+            date: "2023-04-30".parse().unwrap(),
+            summary: "water Boston Fern".to_string(),
+            description: "Every 7 days; last done 2023-04-23, by the window\nor so".to_string(),
+            location: Some("Living room".to_string()),
+        };
+        assert_eq!(event.uid, "default.boston%20fern.water.1@plant-paladin");
+        let stamp = "2023-04-25T10:00:00Z".parse().unwrap();
+        let ics = calendar("Plant care", &[event], stamp);
+        assert!(ics.split("\r\n").all(|l| l.len() <= LINE_LIMIT));
+        let unfolded = ics.replace("\r\n ", "");
+        let lines: Vec<&str> = unfolded.split("\r\n").collect();
+        assert_eq!(lines[0], "BEGIN:VCALENDAR");
+        assert!(lines.contains(&"DTSTAMP:20230425T100000Z"));
+        assert!(lines.contains(&"DTSTART;VALUE=DATE:20230430"));
+        assert!(lines.contains(&"DTEND;VALUE=DATE:20230501"));
+        assert!(lines.contains(&"LOCATION:Living room"));
+        assert!(lines.contains(
+            &"DESCRIPTION:Every 7 days\\; last done 2023-04-23\\, by the window\\nor so"
+        ));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn folds_between_characters() {
+        let mut out = String::new();
+        push_line(&mut out, &"é".repeat(50));
+        let lines: Vec<&str> = out.trim_end().split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 74);
+        assert_eq!(lines[1], format!(" {}", "é".repeat(13)));
+    }
+}
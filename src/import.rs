@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::config::{Config, WATER};
+use crate::state::{EventKind, State};
+use crate::timespec;
+
+/// Other names for tasks, as apps and spreadsheets tend to describe them.
+const TASK_ALIASES: &[(&str, &str)] = &[
+    ("watering", WATER),
+    ("watered", WATER),
+    ("fertilizing", "fertilize"),
+    ("fertilized", "fertilize"),
+    ("fertilising", "fertilize"),
+    ("fertilised", "fertilize"),
+    ("fertilise", "fertilize"),
+    ("misting", "mist"),
+    ("misted", "mist"),
+    ("repotting", "repot"),
+    ("repotted", "repot"),
+    ("rotating", "rotate"),
+    ("rotated", "rotate"),
+    ("cleaning", "clean"),
+    ("cleaned", "clean"),
+    ("pruning", "prune"),
+    ("pruned", "prune"),
+];
+
+/// Which columns of a CSV file hold what.  Names are matched ignoring case.  The task and
+/// note columns are optional: if not given, columns named `task` and `note` are used if
+/// there are any.
+#[derive(Clone, Debug)]
+pub struct Columns {
+    pub plant: String,
+    pub date: String,
+    pub task: Option<String>,
+    pub note: Option<String>,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns {
+            plant: "plant".to_string(),
+            date: "date".to_string(),
+            task: None,
+            note: None,
+        }
+    }
+}
+
+/// How to read a CSV file of care history.
+#[derive(Clone, Debug)]
+pub struct CsvFormat {
+    pub columns: Columns,
+    pub delimiter: u8,
+    /// A strftime format for dates, with or without a time.  If not given, dates are
+    /// `YYYY-MM-DD` with an optional time, or RFC 3339.
+    pub date_format: Option<String>,
+    /// The task of rows without a task column.
+    pub default_task: String,
+}
+
+/// A task done for a plant, as read from a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub plant: String,
+    pub task: String,
+    pub at: NaiveDateTime,
+    pub note: Option<String>,
+}
+
+/// The task `name` refers to, e.g. `water` for "Watering".
+fn task_name(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    match TASK_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        Some((_, task)) => task.to_string(),
+        None => name,
+    }
+}
+
+fn parse_date(s: &str, format: Option<&str>) -> Result<NaiveDateTime> {
+    let Some(format) = format else {
+        return timespec::parse_timestamp(s);
+    };
+    let s = s.trim();
+    NaiveDateTime::parse_from_str(s, format)
+        .or_else(|_| NaiveDate::parse_from_str(s, format).map(|d| d.and_hms_opt(0, 0, 0).unwrap()))
+        .map_err(|_| anyhow!("invalid date {s:?}, expected {format}"))
+}
+
+/// Read care history from CSV with a header row.  Rows with an empty plant are ignored.
+pub fn parse_csv(input: &str, format: &CsvFormat) -> Result<Vec<Row>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(format.delimiter)
+        .trim(csv::Trim::All)
+        .from_reader(input.as_bytes());
+    let headers = reader.headers().context("invalid CSV")?.clone();
+    let find = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let required = |name: &str| {
+        find(name).ok_or_else(|| {
+            let found: Vec<&str> = headers.iter().collect();
+            anyhow!("no column named {name:?}, found {}", found.join(", "))
+        })
+    };
+    let optional = |name: &Option<String>, default: &str| match name {
+        Some(name) => required(name).map(Some),
+        None => Ok(find(default)),
+    };
+    let plant_column = required(&format.columns.plant)?;
+    let date_column = required(&format.columns.date)?;
+    let task_column = optional(&format.columns.task, "task")?;
+    let note_column = optional(&format.columns.note, "note")?;
+
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        // Line numbers as a spreadsheet shows them, counting the header.
+        let line = i + 2;
+        let record = record.with_context(|| format!("invalid CSV on line {line}"))?;
+        let field = |column: usize| record.get(column).unwrap_or_default();
+        if field(plant_column).is_empty() {
+            continue;
+        }
+        let task = match task_column.map(field) {
+            Some(task) if !task.is_empty() => task_name(task),
+            Some(_) => bail!("no task on line {line}"),
+            None => format.default_task.clone(),
+        };
+        let at = parse_date(field(date_column), format.date_format.as_deref())
+            .with_context(|| format!("line {line}"))?;
+        let note = note_column
+            .map(field)
+            .filter(|n| !n.is_empty())
+            .map(String::from);
+        rows.push(Row {
+            plant: field(plant_column).to_string(),
+            task,
+            at,
+            note,
+        });
+    }
+    Ok(rows)
+}
+
+/// Events added for one plant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Added {
+    pub count: usize,
+    pub first: NaiveDateTime,
+    pub last: NaiveDateTime,
+}
+
+/// What [`merge`] did, or would have done.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub added: BTreeMap<String, Added>,
+    /// Plants which are not in the config, with the tasks imported for them, if they were
+    /// created.
+    pub created: BTreeMap<String, BTreeSet<String>>,
+    /// Rows for a task already recorded for the plant on the same day.
+    pub duplicates: usize,
+    /// Number of rows for each plant skipped for not being in the config.
+    pub unknown_plants: BTreeMap<String, usize>,
+    /// Number of rows for each plant and task skipped as the plant doesn't have the task.
+    pub unknown_tasks: BTreeMap<(String, String), usize>,
+}
+
+impl Report {
+    pub fn total(&self) -> usize {
+        self.added.values().map(|a| a.count).sum()
+    }
+}
+
+/// Record `rows` as tasks done in `state`, skipping any for a task already done for the
+/// plant on the same day.  Plants not in `config` are skipped unless `create` is set, in
+/// which case they are added to `state` and reported in [`Report::created`], to be added to
+/// the config.
+pub fn merge(rows: Vec<Row>, config: &Config, state: &mut State, create: bool) -> Report {
+    let mut report = Report::default();
+    for row in rows {
+        let created = report
+            .created
+            .keys()
+            .find(|p| p.to_lowercase() == row.plant.to_lowercase())
+            .cloned();
+        let plant = match (config.find_plant(&row.plant), created) {
+            (Some(plant), _) => {
+                if !config.plants[plant].tasks.contains_key(&row.task) {
+                    let key = (plant.to_string(), row.task);
+                    *report.unknown_tasks.entry(key).or_default() += 1;
+                    continue;
+                }
+                plant.to_string()
+            }
+            (None, Some(plant)) => plant,
+            (None, None) if create => {
+                report.created.insert(row.plant.clone(), BTreeSet::new());
+                row.plant.clone()
+            }
+            (None, None) => {
+                *report.unknown_plants.entry(row.plant).or_default() += 1;
+                continue;
+            }
+        };
+        if let Some(tasks) = report.created.get_mut(&plant) {
+            tasks.insert(row.task.clone());
+        }
+        let status = state.plants.entry(plant.clone()).or_default();
+        let duplicate = status.history.iter().any(|e| {
+            e.kind == EventKind::Done && e.task == row.task && e.at.date() == row.at.date()
+        });
+        if duplicate {
+            report.duplicates += 1;
+            continue;
+        }
+        status.record(row.at, &row.task, EventKind::Done, row.note);
+        report
+            .added
+            .entry(plant)
+            .and_modify(|a| {
+                a.count += 1;
+                a.first = a.first.min(row.at);
+                a.last = a.last.max(row.at);
+            })
+            .or_insert(Added {
+                count: 1,
+                first: row.at,
+                last: row.at,
+            });
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format() -> CsvFormat {
+        CsvFormat {
+            columns: Columns::default(),
+            delimiter: b',',
+            date_format: None,
+            default_task: WATER.to_string(),
+        }
+    }
+
+    #[test]
+    fn reads_csv() -> Result<()> {
+        let csv = "Date,Plant,Note\n2023-04-01,fern,\n2023-04-08 09:30,Fern,dry\n,,\n";
+        let rows = parse_csv(csv, &format())?;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].task, WATER);
+        assert_eq!(rows[0].at, "2023-04-01T00:00:00".parse()?);
+        assert_eq!(rows[1].note.as_deref(), Some("dry"));
+
+        let other = "Name;Action;When\nMonstera;Fertilizing;14/05/2023\n";
+        let mut custom = format();
+        custom.columns.plant = "name".to_string();
+        custom.columns.date = "when".to_string();
+        custom.columns.task = Some("action".to_string());
+        custom.delimiter = b';';
+        custom.date_format = Some("%d/%m/%Y".to_string());
+        let rows = parse_csv(other, &custom)?;
+        assert_eq!(rows[0].task, "fertilize");
+        assert_eq!(rows[0].at, "2023-05-14T00:00:00".parse()?);
+
+        let mut custom = format();
+        custom.columns.task = Some("action".to_string());
+        let err = parse_csv(csv, &custom).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no column named \"action\", found Date, Plant, Note"
+        );
+        let err = parse_csv("plant,date\nfern,soon\n", &format()).unwrap_err();
+        assert_eq!(err.to_string(), "line 2");
+        Ok(())
+    }
+
+    #[test]
+    fn merges_without_duplicates() -> Result<()> {
+        let config: Config = toml::from_str("[fern]\nwatering_interval = 7")?;
+        let mut state = State::default();
+        let row = |plant: &str, task: &str, at: &str| Row {
+            plant: plant.to_string(),
+            task: task.to_string(),
+            at: at.parse().unwrap(),
+            note: None,
+        };
+        let rows = vec![
+            row("FERN", WATER, "2023-04-01T08:00:00"),
+            row("fern", WATER, "2023-04-01T20:00:00"),
+            row("fern", WATER, "2023-04-09T08:00:00"),
+            row("fern", "mist", "2023-04-09T08:00:00"),
+            row("aloe", WATER, "2023-04-09T08:00:00"),
+        ];
+        let report = merge(rows.clone(), &config, &mut state, false);
+        assert_eq!(report.total(), 2);
+        assert_eq!(report.added["fern"].first, "2023-04-01T08:00:00".parse()?);
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(report.unknown_plants["aloe"], 1);
+        assert_eq!(
+            report.unknown_tasks[&("fern".to_string(), "mist".to_string())],
+            1
+        );
+        assert_eq!(state.plants["fern"].history.len(), 2);
+
+        let report = merge(rows, &config, &mut state, true);
+        assert_eq!(report.total(), 1);
+        assert_eq!(report.duplicates, 3);
+        assert_eq!(report.created["aloe"], BTreeSet::from([WATER.to_string()]));
+        assert_eq!(state.plants["aloe"].history.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{Event, PlantStatus, State};
+
+/// Number of entries kept in the journal; older ones are dropped.
+pub const JOURNAL_LEN: usize = 100;
+
+/// A single mutation of the state, and perhaps the config, recording enough to revert it
+/// exactly.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub at: NaiveDateTime,
+    /// The command line which made the change.
+    pub command: String,
+    /// Statuses of plants which changed, as they were before the change.
+    #[serde(default)]
+    pub before: BTreeMap<String, PlantStatus>,
+    /// Plants which did not exist in the state before the change.
+    #[serde(default)]
+    pub added: BTreeSet<String>,
+    /// The config file as it was before the change, if the change edited it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<String>,
+}
+
+impl JournalEntry {
+    /// Record the change from `before` to `after`, along with the previous contents of the
+    /// config if it changed too, or `None` if nothing changed.
+    pub fn new(
+        at: NaiveDateTime,
+        command: String,
+        before: &State,
+        after: &State,
+        config: Option<String>,
+    ) -> Option<Self> {
+        let mut entry = JournalEntry {
+            at,
+            command,
+            before: BTreeMap::new(),
+            added: BTreeSet::new(),
+            config,
+        };
+        for (plant, status) in &before.plants {
+            if after.plants.get(plant) != Some(status) {
+                entry.before.insert(plant.clone(), status.clone());
+            }
+        }
+        for plant in after.plants.keys() {
+            if !before.plants.contains_key(plant) {
+                entry.added.insert(plant.clone());
+            }
+        }
+        if entry.before.is_empty() && entry.added.is_empty() && entry.config.is_none() {
+            None
+        } else {
+            Some(entry)
+        }
+    }
+
+    /// Describe the events that reverting this entry would remove from and restore to `state`.
+    pub fn preview(&self, state: &State) -> Vec<String> {
+        let empty = PlantStatus::default();
+        let plants: BTreeSet<_> = self.before.keys().chain(&self.added).collect();
+        let mut lines = Vec::new();
+        for plant in plants {
+            let current = state.plants.get(plant).unwrap_or(&empty);
+            let before = self.before.get(plant).unwrap_or(&empty);
+            let describe = |e: &Event| {
+                format!(
+                    "{plant}: {} {} at {}",
+                    e.task,
+                    e.kind,
+                    e.at.format("%Y-%m-%d %H:%M")
+                )
+            };
+            for e in current
+                .history
+                .iter()
+                .filter(|e| !before.history.contains(e))
+            {
+                lines.push(format!("remove  {}", describe(e)));
+            }
+            for e in before
+                .history
+                .iter()
+                .filter(|e| !current.history.contains(e))
+            {
+                lines.push(format!("restore {}", describe(e)));
+            }
+            let removed = current
+                .readings
+                .iter()
+                .filter(|r| !before.readings.contains(r))
+                .count();
+            let restored = before
+                .readings
+                .iter()
+                .filter(|r| !current.readings.contains(r))
+                .count();
+            if removed > 0 {
+                lines.push(format!("remove  {plant}: {removed} readings"));
+            }
+            if restored > 0 {
+                lines.push(format!("restore {plant}: {restored} readings"));
+            }
+        }
+        if self.config.is_some() {
+            lines.push("restore config file as it was before".to_string());
+        }
+        lines
+    }
+
+    pub fn revert(&self, state: &mut State) {
+        for plant in &self.added {
+            state.plants.remove(plant);
+        }
+        for (plant, status) in &self.before {
+            state.plants.insert(plant.clone(), status.clone());
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    /// Oldest first.
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    pub fn push(&mut self, entry: JournalEntry) {
+        self.entries.push(entry);
+        if self.entries.len() > JOURNAL_LEN {
+            self.entries.drain(..self.entries.len() - JOURNAL_LEN);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::EventKind;
+
+    #[test]
+    fn revert_restores_exactly() {
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        let mut state = State::default();
+        state.plants.entry("fern".to_string()).or_default().record(
+            t("2023-04-01T08:00:00"),
+            "water",
+            EventKind::Done,
+            None,
+        );
+        let original = state.clone();
+
+        state.plants.get_mut("fern").unwrap().record(
+            t("2023-04-08T08:00:00"),
+            "water",
+            EventKind::Done,
+            None,
+        );
+        state
+            .plants
+            .insert("cactus".to_string(), PlantStatus::default());
+        let entry = JournalEntry::new(
+            t("2023-04-08T08:00:00"),
+            "water -a".into(),
+            &original,
+            &state,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            entry.preview(&state),
+            ["remove  fern: water done at 2023-04-08 08:00"]
+        );
+
+        entry.revert(&mut state);
+        assert_eq!(state.plants, original.plants);
+        assert!(JournalEntry::new(
+            t("2023-04-08T08:00:00"),
+            "nag".into(),
+            &state,
+            &original,
+            None
+        )
+        .is_none());
+    }
+}
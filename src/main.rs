@@ -1,70 +1,52 @@
-use std::{
-    collections::HashMap,
-    path::{Path, PathBuf},
-};
+use std::path::Path;
 
 use anyhow::{anyhow, bail, Context, Result};
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
-use clap::Parser;
-use directories::ProjectDirs;
+use chrono::{Days, Duration, NaiveDate, NaiveDateTime};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use posix_cli_utils::IoContext;
-use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde::{de::DeserializeOwned, Serialize};
 
-const DEFAULT_CONFIG_TOML: &str = include_str!("../default-config.toml");
-
-pub fn deserialize_string_lowercase<'de, D>(deserializer: D) -> Result<String, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let mut s = String::deserialize(deserializer)?;
-    s.make_ascii_lowercase();
-    Ok(s)
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Plant {
-    pub watering_interval: u64,
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-pub struct Config {
-    #[serde(flatten)]
-    pub plants: HashMap<String, Plant>,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct PlantStatus {
-    pub last_watered: NaiveDateTime,
-}
+mod adapt;
+mod complete;
+mod config;
+mod config_edit;
+mod daemon;
+mod due;
+mod fsutil;
+mod ics;
+mod import;
+mod journal;
+mod names;
+mod notify;
+mod output;
+mod paths;
+mod profiles;
+mod readings;
+mod schedule;
+mod state;
+mod stats;
+mod timespec;
+use config::*;
+use config_edit::ConfigDocument;
+use due::*;
+use fsutil::{write_atomic, Lock};
+use journal::*;
+use output::*;
+use paths::{PathArgs, Paths};
+use profiles::Profiles;
+use state::*;
 
-impl Default for PlantStatus {
-    fn default() -> Self {
-        Self {
-            last_watered: NaiveDateTime::new(
-                NaiveDate::from_ymd_opt(1900, 1, 1).unwrap(),
-                NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
-            ),
-        }
-    }
-}
-
-#[derive(Clone, Default, Serialize, Deserialize)]
-pub struct State {
-    pub plants: HashMap<String, PlantStatus>,
-}
-
-fn state_path(dirs: &ProjectDirs) -> PathBuf {
-    dirs.config_dir().join("state.toml")
-}
+const DEFAULT_CONFIG_TOML: &str = include_str!("../default-config.toml");
 
-fn config_path(dirs: &ProjectDirs) -> PathBuf {
-    dirs.config_dir().join("config.toml")
+/// Take the lock which serialises commands that modify the config or state.  It must be
+/// held across the whole load-modify-write cycle.
+fn lock(paths: &Paths) -> Result<Lock> {
+    Lock::acquire(&paths.lock())
 }
 
 fn write_toml<T: Serialize, P: AsRef<Path>>(val: T, path: P) -> Result<()> {
     let contents = toml::to_string_pretty(&val)?;
-    let path = path.as_ref();
-    std::fs::write(path, contents).context_write(path)
+    write_atomic(path.as_ref(), contents.as_bytes())
 }
 
 fn read_toml<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T> {
@@ -73,19 +55,25 @@ fn read_toml<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T> {
     toml::from_str(&contents).context("failed to deserialise")
 }
 
-fn load_config(dirs: &ProjectDirs) -> Result<Config> {
-    let path = config_path(dirs);
+fn load_config(paths: &Paths) -> Result<Config> {
+    let path = paths.config.clone();
     if path.exists() {
         read_toml(path)
     } else {
         println!("no config exists, create config at {}", path.display());
-        std::fs::write(&path, DEFAULT_CONFIG_TOML).context_write(&path)?;
+        write_atomic(&path, DEFAULT_CONFIG_TOML.as_bytes())?;
         Ok(toml::from_str(DEFAULT_CONFIG_TOML).unwrap())
     }
 }
 
-fn load_state(dirs: &ProjectDirs) -> Result<State> {
-    let path = state_path(dirs);
+fn load_config_document(paths: &Paths) -> Result<ConfigDocument> {
+    let path = paths.config.clone();
+    let contents = std::fs::read_to_string(&path).context_read(&path)?;
+    ConfigDocument::parse(&contents)
+}
+
+fn load_state(paths: &Paths) -> Result<State> {
+    let path = paths.state.clone();
     if path.exists() {
         read_toml(path)
     } else {
@@ -93,12 +81,71 @@ fn load_state(dirs: &ProjectDirs) -> Result<State> {
     }
 }
 
-fn write_state(dirs: &ProjectDirs, state: &State) -> Result<()> {
-    let path = state_path(dirs);
-    write_toml(state, path)
+fn load_journal(paths: &Paths) -> Result<Journal> {
+    let path = paths.journal();
+    if path.exists() {
+        read_toml(path)
+    } else {
+        Ok(Journal::default())
+    }
+}
+
+/// The command line this process was invoked with, for the journal.
+fn command_line() -> String {
+    let args: Vec<_> = std::env::args()
+        .skip(1)
+        .map(|a| if a.contains(' ') { format!("{a:?}") } else { a })
+        .collect();
+    args.join(" ")
+}
+
+/// Write the state, recording what changed in the journal so it can be undone.
+fn write_state(paths: &Paths, state: &State) -> Result<()> {
+    write_changes(paths, state, None)
+}
+
+/// Write the state and, if given, the config, recording what changed in the journal so it
+/// can be undone.
+fn write_changes(paths: &Paths, state: &State, doc: Option<&ConfigDocument>) -> Result<()> {
+    let previous = load_state(paths)?;
+    let mut config = None;
+    if let Some(doc) = doc {
+        let path = paths.config.clone();
+        let old = std::fs::read_to_string(&path).context_read(&path)?;
+        let new = doc.to_string();
+        if new != old {
+            config = Some((old, new));
+        }
+    }
+    let now = chrono::Local::now().naive_local();
+    let old_config = config.as_ref().map(|(old, _)| old.clone());
+    if let Some(entry) = JournalEntry::new(now, command_line(), &previous, state, old_config) {
+        let mut journal = load_journal(paths)?;
+        journal.push(entry);
+        write_toml(&journal, paths.journal())?;
+    }
+    write_toml(state, paths.state.clone())?;
+    if let Some((_, new)) = config {
+        write_atomic(&paths.config, new.as_bytes())?;
+    }
+    Ok(())
 }
 
+/// Make the state's plants match the config's.  History is kept for plants whose name in
+/// the config changed only in case.
 fn sync_state_with_config(config: &Config, state: &mut State) {
+    let renamed: Vec<_> = state
+        .plants
+        .keys()
+        .filter(|plant| !config.plants.contains_key(*plant))
+        .filter_map(|plant| Some((plant.clone(), config.find_plant(plant)?.to_string())))
+        .collect();
+    for (old, new) in renamed {
+        if !state.plants.contains_key(&new) {
+            let status = state.plants.remove(&old).unwrap();
+            state.plants.insert(new, status);
+        }
+    }
     state
         .plants
         .retain(|plant, _| config.plants.contains_key(plant));
@@ -109,79 +156,1584 @@ fn sync_state_with_config(config: &Config, state: &mut State) {
     }
 }
 
-fn cmd_water(dirs: &ProjectDirs, args: WaterArgs) -> Result<()> {
-    let config = load_config(dirs)?;
-    let mut state = load_state(dirs)?;
+fn cmd_do(paths: &Paths, task: &str, args: DoArgs) -> Result<()> {
+    let _lock = lock(paths)?;
+    let config = load_config(paths)?;
+    let mut state = load_state(paths)?;
     sync_state_with_config(&config, &mut state);
     let now = chrono::Local::now().naive_local();
-    if args.all {
-        for (name, plant) in &config.plants {
-            let status = state.plants.get_mut(name).unwrap();
-            if (now - status.last_watered).num_days() >= plant.watering_interval as i64 {
-                status.last_watered = now;
+    let at = match &args.at {
+        Some(at) => timespec::parse_when(at, now)?,
+        None => now,
+    };
+    if at > now {
+        bail!(
+            "cannot record {task} in the future ({})",
+            at.format("%Y-%m-%d %H:%M")
+        )
+    }
+    let plants = if args.all || !args.select.is_empty() {
+        args.select.check(&config)?;
+        task_statuses(&config, &state, at)
+            .into_iter()
+            .filter(|ts| ts.task == task && (!args.all || ts.is_due(at)))
+            .filter(|ts| args.select.matches(&config.plants[&ts.plant]))
+            .map(|ts| ts.plant)
+            .collect()
+    } else {
+        let mut plants = Vec::new();
+        for name in &args.plants {
+            let plant = config.resolve_plant(name)?;
+            if !config.plants[plant].tasks.contains_key(task) {
+                bail!("plant {plant} has no {task} task in config")
             }
+            plants.push(plant.to_string());
         }
-    } else {
-        for plant in &args.plants {
-            if !config.plants.contains_key(&**plant) {
-                bail!("no plant named {plant} in config")
+        plants
+    };
+    for plant in &plants {
+        let status = state.plants.get_mut(plant).unwrap();
+        match status.last_done(task) {
+            Some(last) if last > at && !args.force => bail!(
+                "{task} {plant} was last recorded at {}, which is after {} (use --force to record it anyway)",
+                last.format("%Y-%m-%d %H:%M"),
+                at.format("%Y-%m-%d %H:%M")
+            ),
+            _ => status.record(at, task, EventKind::Done, args.note.clone()),
+        }
+    }
+
+    let mut doc = None;
+    if config.settings.adaptive.enabled {
+        let suggestions: Vec<_> = plants
+            .iter()
+            .map(|plant| {
+                let interval = &config.plants[plant].tasks[task];
+                adapt::suggest(
+                    plant,
+                    task,
+                    interval,
+                    &state.plants[plant],
+                    &config.settings.adaptive,
+                )
+            })
+            .collect();
+        let mut edited = load_config_document(paths)?;
+        apply_suggestions(&mut edited, &suggestions)?;
+        doc = Some(edited);
+    }
+    write_changes(paths, &state, doc.as_ref())
+}
+
+/// Change intervals in the config as suggested, reporting each change.
+fn apply_suggestions(doc: &mut ConfigDocument, suggestions: &[adapt::Suggestion]) -> Result<()> {
+    for s in suggestions {
+        if let (Some(current), Some(days)) = (s.current, s.change()) {
+            doc.set_task(&s.plant, &s.task, days)?;
+            println!(
+                "Changed {} interval of {} from {current} to {days} days (done every {:.1} days lately)",
+                s.task,
+                s.plant,
+                s.observed.unwrap_or_default()
+            );
+        }
+    }
+    doc.config()?;
+    Ok(())
+}
+
+/// The tasks which are due among the selected plants, grouped by room with the most
+/// urgent first within each room.  Plants without a room come last.
+fn due_records(paths: &Paths, config: &Config, select: &Selection) -> Result<Vec<TaskRecord>> {
+    let now = chrono::Local::now().naive_local();
+    let mut state = load_state(paths)?;
+    sync_state_with_config(config, &mut state);
+    let mut records: Vec<_> = task_statuses(config, &state, now)
+        .iter()
+        .filter(|ts| ts.is_due(now) && select.matches(&config.plants[&ts.plant]))
+        .map(|ts| TaskRecord::new(ts, now))
+        .collect();
+    records.sort_by_key(|r| (r.room.is_none(), r.room.as_ref().map(|r| r.to_lowercase())));
+    Ok(records)
+}
+
+/// Send a notification through each notifier, reporting failures with `report`.  Fails if
+/// any notifier did.
+fn send_notification(
+    notifiers: &[Box<dyn notify::Notifier>],
+    notification: &notify::Notification,
+    report: impl Fn(&str),
+) -> Result<()> {
+    let mut failed = 0;
+    for notifier in notifiers {
+        if let Err(e) = notifier.notify(notification) {
+            report(&format!("{} notifier failed: {e:#}", notifier.name()));
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("{failed} of {} notifiers failed", notifiers.len())
+    }
+    Ok(())
+}
+
+fn configured_notifiers(config: &Config) -> Result<Vec<Box<dyn notify::Notifier>>> {
+    if config.notify.is_empty() {
+        bail!("no notifiers are configured, add a [notify] section to the config")
+    }
+    Ok(notify::notifiers(&config.notify))
+}
+
+fn cmd_nag(paths: &Paths, format: Format, args: NagArgs) -> Result<()> {
+    let NagArgs { notify, select } = args;
+    let config = load_config(paths)?;
+    select.check(&config)?;
+    let records = due_records(paths, &config, &select)?;
+    if notify {
+        let notifiers = configured_notifiers(&config)?;
+        if records.is_empty() {
+            return Ok(());
+        }
+        let notification = notify::Notification::new(records);
+        return send_notification(&notifiers, &notification, |e| eprintln!("{e}"));
+    }
+    print_records(format, &records, |records| {
+        let grouped = records.iter().any(|r| r.room.is_some());
+        let mut current_room = None;
+        for r in records {
+            if grouped {
+                let room = r.room.as_deref().map(str::to_lowercase).unwrap_or_default();
+                if current_room.as_ref() != Some(&room) {
+                    println!("{}:", r.room.as_deref().unwrap_or("No room"));
+                    current_room = Some(room);
+                }
             }
+            let indent = if grouped { "  " } else { "" };
+            println!("{indent}Due: {}", r.summary());
         }
-        for plant in &args.plants {
-            state.plants.get_mut(plant).unwrap().last_watered = now;
+    })
+}
+
+fn daemon_check(paths: &Paths, config: &Config, args: &DaemonArgs) -> Result<()> {
+    let records = due_records(paths, config, &args.select)?;
+    if records.is_empty() {
+        daemon::log("nothing is due");
+        return Ok(());
+    }
+    daemon::log(&format!("{} tasks due, sending reminders", records.len()));
+    let notification = notify::Notification::new(records);
+    let notifiers = match config.notify.is_empty() {
+        true => vec![Box::new(notify::Terminal) as Box<dyn notify::Notifier>],
+        false => notify::notifiers(&config.notify),
+    };
+    send_notification(&notifiers, &notification, daemon::log)
+}
+
+fn cmd_notify(paths: &Paths, cmd: NotifyCommand) -> Result<()> {
+    match cmd {
+        NotifyCommand::Test => {
+            let config = load_config(paths)?;
+            let notification = notify::Notification::test();
+            let notifiers = configured_notifiers(&config)?;
+            let mut failed = 0;
+            for notifier in &notifiers {
+                match notifier.notify(&notification) {
+                    Ok(()) => println!("{}: sent", notifier.name()),
+                    Err(e) => {
+                        println!("{}: failed: {e:#}", notifier.name());
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                bail!("{failed} of {} notifiers failed", notifiers.len())
+            }
+            Ok(())
         }
+    }
+}
+
+fn cmd_daemon(paths: &Paths, args: DaemonArgs) -> Result<()> {
+    use std::sync::atomic::Ordering;
+
+    let shutdown = daemon::shutdown_flag()?;
+    let mut config = load_config(paths)?;
+    args.select.check(&config)?;
+    let mut watched = daemon::Watched::new(&paths.config);
+    let now = chrono::Local::now().naive_local();
+    let mut next = match args.now {
+        true => now,
+        false => daemon::next_run(now, &config.settings.daemon.times),
     };
+    daemon::log(&format!(
+        "started with config {}, next check at {}",
+        paths.config.display(),
+        next.format("%Y-%m-%d %H:%M")
+    ));
+    while !shutdown.load(Ordering::Relaxed) {
+        let now = chrono::Local::now().naive_local();
+        if watched.changed() {
+            match read_toml::<Config, _>(&paths.config) {
+                Ok(new) => {
+                    config = new;
+                    if next > now {
+                        next = daemon::next_run(now, &config.settings.daemon.times);
+                    }
+                    daemon::log(&format!(
+                        "reloaded config, next check at {}",
+                        next.format("%Y-%m-%d %H:%M")
+                    ));
+                }
+                Err(e) => daemon::log(&format!("keeping the previous config: {e:#}")),
+            }
+        }
+        if now >= next {
+            if let Err(e) = daemon_check(paths, &config, &args) {
+                daemon::log(&format!("check failed: {e:#}"));
+            }
+            next = daemon::next_run(now, &config.settings.daemon.times);
+            daemon::log(&format!("next check at {}", next.format("%Y-%m-%d %H:%M")));
+        }
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    daemon::log("shutting down");
+    Ok(())
+}
 
-    write_state(dirs, &state)
+fn cmd_status(paths: &Paths, format: Format, select: Selection) -> Result<()> {
+    let now = chrono::Local::now().naive_local();
+    let config = load_config(paths)?;
+    select.check(&config)?;
+    let mut state = load_state(paths)?;
+    sync_state_with_config(&config, &mut state);
+    let records: Vec<_> = task_statuses(&config, &state, now)
+        .iter()
+        .filter(|ts| select.matches(&config.plants[&ts.plant]))
+        .map(|ts| TaskRecord::new(ts, now))
+        .collect();
+    print_records(format, &records, |records| {
+        let with_rooms = records.iter().any(|r| r.room.is_some());
+        let mut rows: Vec<_> = records
+            .iter()
+            .map(|r| {
+                let last_done = r
+                    .last_done
+                    .map(|t| t.format("%Y-%m-%d").to_string())
+                    .unwrap_or_else(|| "never".to_string());
+                let next_due = r
+                    .due_date
+                    .map(|d| d.format("%Y-%m-%d").to_string())
+                    .unwrap_or_else(|| "now".to_string());
+                let remaining = match (r.snoozed_until, r.days_until_due) {
+                    (Some(until), _) => {
+                        format!("snoozed until {}", until.format("%Y-%m-%d %H:%M"))
+                    }
+                    (None, _) if r.dry => {
+                        format!("dry, moisture {}", r.moisture.unwrap_or_default())
+                    }
+                    (None, None) => "due".to_string(),
+                    (None, Some(0)) => "due today".to_string(),
+                    (None, Some(1)) => "in 1 day".to_string(),
+                    (None, Some(-1)) => "1 day overdue".to_string(),
+                    (None, Some(d)) if d > 0 => format!("in {d} days"),
+                    (None, Some(d)) => format!("{} days overdue", -d),
+                };
+                vec![
+                    r.plant.clone(),
+                    r.room.clone().unwrap_or_default(),
+                    r.task.clone(),
+                    last_done,
+                    format!("{}d", r.interval_days),
+                    next_due,
+                    remaining,
+                ]
+            })
+            .collect();
+        let mut header = vec![
+            "PLANT",
+            "ROOM",
+            "TASK",
+            "LAST DONE",
+            "EVERY",
+            "NEXT DUE",
+            "STATUS",
+        ];
+        if !with_rooms {
+            header.remove(1);
+            for row in &mut rows {
+                row.remove(1);
+            }
+        }
+        print_table(&header, &rows);
+    })
 }
 
-fn cmd_nag(dirs: &ProjectDirs) -> Result<()> {
+fn cmd_snooze(paths: &Paths, args: SnoozeArgs) -> Result<()> {
+    let _lock = lock(paths)?;
+    let config = load_config(paths)?;
+    let mut state = load_state(paths)?;
+    sync_state_with_config(&config, &mut state);
     let now = chrono::Local::now().naive_local();
-    let mut state = load_state(dirs)?;
-    let config = load_config(dirs)?;
+    let name = config.resolve_plant(&args.plant)?;
+    let plant = &config.plants[name];
+    let until = match (&args.duration, &args.until) {
+        (_, Some(until)) => timespec::parse_datetime(until)?,
+        (Some(duration), None) => now
+            .checked_add_signed(timespec::parse_duration(duration)?)
+            .ok_or_else(|| anyhow!("cannot snooze for {duration}"))?,
+        (None, None) => now + Duration::days(1),
+    };
+    if until <= now {
+        bail!("snooze must end in the future")
+    }
+    let tasks: Vec<_> = match &args.task {
+        Some(task) if !plant.tasks.contains_key(task) => {
+            bail!("plant {name} has no {task} task in config")
+        }
+        Some(task) => vec![task.as_str()],
+        None => plant.tasks.keys().map(String::as_str).collect(),
+    };
+    let status = state.plants.get_mut(name).unwrap();
+    for task in tasks {
+        status.snooze(now, task, until, args.note.clone());
+        println!(
+            "Snoozed {task} {name} until {}",
+            until.format("%Y-%m-%d %H:%M")
+        );
+    }
+    write_state(paths, &state)
+}
+
+fn parse_task_interval(s: &str) -> Result<(String, u64)> {
+    let (task, days) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected TASK=DAYS, e.g. fertilize=30"))?;
+    let days = days
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid number of days: {days:?}"))?;
+    Ok((task.trim().to_string(), schedule::check_days(days)?))
+}
+
+fn cmd_plant(paths: &Paths, cmd: PlantCommand) -> Result<()> {
+    let _lock = lock(paths)?;
+    let config = load_config(paths)?;
+    let mut doc = load_config_document(paths)?;
+    let mut state = load_state(paths)?;
     sync_state_with_config(&config, &mut state);
-    for (plant, status) in state.plants {
-        let days = (now - status.last_watered).num_days();
-        let &Plant {
-            watering_interval: watering_frequency,
-        } = config.plants.get(&plant).unwrap();
-        if watering_frequency as i64 <= days {
+    match cmd {
+        PlantCommand::Add(mut args) => {
+            validate_plant_name(&args.name)?;
+            if let Some(existing) = config.find_plant(&args.name) {
+                bail!("plant {existing} already exists")
+            }
+            if let Some(days) = args.interval {
+                args.tasks.insert(0, (WATER.to_string(), days));
+            }
+            // A plant without tasks would never fall due.
+            if args.tasks.is_empty() {
+                bail!(
+                    "give {} a task, e.g. --interval 7 to water it weekly or --task mist=3",
+                    args.name
+                )
+            }
+            doc.add_plant(&args.name, &args.tasks);
+            if let Some(room) = &args.room {
+                doc.set_room(&args.name, Some(room))?;
+            }
+            for tag in &args.tags {
+                doc.add_tag(&args.name, tag)?;
+            }
+            state
+                .plants
+                .insert(args.name.clone(), PlantStatus::default());
+            println!("Added {}", args.name);
+        }
+        PlantCommand::Remove(args) => {
+            let name = config.resolve_plant(&args.name)?;
+            // Removal drops the plant's history, so don't let a prefix choose the plant.
+            if name.to_lowercase() != args.name.to_lowercase() {
+                bail!("give the full name to remove {name}")
+            }
+            doc.remove_plant(name)?;
+            state.plants.remove(name);
+            println!("Removed {name}");
+        }
+        PlantCommand::Rename(args) => {
+            let old = config.resolve_plant(&args.old)?;
+            validate_plant_name(&args.new)?;
+            if let Some(existing) = config.find_plant(&args.new).filter(|p| *p != old) {
+                bail!("plant {existing} already exists")
+            }
+            doc.rename_plant(old, &args.new)?;
+            let status = state.plants.remove(old).unwrap_or_default();
+            state.plants.insert(args.new.clone(), status);
+            println!("Renamed {old} to {}", args.new);
+        }
+        PlantCommand::Edit(args) => {
+            let name = config.resolve_plant(&args.name)?;
+            if let Some(days) = args.interval {
+                doc.set_task(name, WATER, days)?;
+            }
+            for (task, days) in &args.tasks {
+                doc.set_task(name, task, *days)?;
+            }
+            for task in &args.remove_tasks {
+                if !doc.remove_task(name, task)? {
+                    bail!("plant {name} has no {task} task in config")
+                }
+            }
+            if doc.config()?.plants[name].tasks.is_empty() {
+                bail!(
+                    "cannot remove the last task of {name}, use `plant remove` to remove the plant"
+                )
+            }
+            if let Some(room) = &args.room {
+                let room = Some(room.as_str()).filter(|r| !r.is_empty());
+                doc.set_room(name, room)?;
+            }
+            for tag in &args.tags {
+                doc.add_tag(name, tag)?;
+            }
+            for tag in &args.remove_tags {
+                if !doc.remove_tag(name, tag)? {
+                    bail!("plant {name} has no {tag} tag")
+                }
+            }
+            println!("Updated {name}");
+        }
+    }
+    doc.config()?;
+    write_changes(paths, &state, Some(&doc))
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    use std::io::Write;
+    print!("{prompt} [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn cmd_undo(paths: &Paths, args: UndoArgs) -> Result<()> {
+    let _lock = lock(paths)?;
+    let mut journal = load_journal(paths)?;
+    let mut state = load_state(paths)?;
+    if journal.entries.is_empty() {
+        println!("Nothing to undo");
+        return Ok(());
+    }
+    let count = args.count.min(journal.entries.len());
+    let undone = journal.entries.split_off(journal.entries.len() - count);
+    let mut config = None;
+    for entry in undone.iter().rev() {
+        println!(
+            "Undo `{}` from {}:",
+            entry.command,
+            entry.at.format("%Y-%m-%d %H:%M")
+        );
+        for line in entry.preview(&state) {
+            println!("  {line}");
+        }
+        entry.revert(&mut state);
+        // Entries are undone newest first, so the oldest one's config is the one to keep.
+        config = entry.config.as_ref().or(config);
+    }
+    if !args.yes && !confirm("Apply?")? {
+        println!("Nothing changed");
+        return Ok(());
+    }
+    if let Some(config) = config {
+        write_atomic(&paths.config, config.as_bytes())?;
+    }
+    write_toml(&state, paths.state.clone())?;
+    write_toml(&journal, paths.journal())
+}
+
+fn cmd_paths(paths: &Paths) -> Result<()> {
+    println!("profile: {}", paths.profile);
+    println!(
+        "config:  {} ({})",
+        paths.config.display(),
+        paths.config_source
+    );
+    println!(
+        "state:   {} ({})",
+        paths.state.display(),
+        paths.state_source
+    );
+    println!("journal: {}", paths.journal().display());
+    println!("lock:    {}", paths.lock().display());
+    Ok(())
+}
+
+fn cmd_profile(cmd: ProfileCommand) -> Result<()> {
+    let profiles = Profiles::from_project_dirs()?;
+    match cmd {
+        ProfileCommand::List => {
+            let default = profiles.default_profile()?;
+            for name in profiles.list()? {
+                let marker = if name == default { "*" } else { " " };
+                println!("{marker} {name}");
+            }
+        }
+        ProfileCommand::Create(args) => {
+            profiles.create(&args.name)?;
+            let config = profiles.config_dir(&args.name).join("config.toml");
+            write_atomic(&config, DEFAULT_CONFIG_TOML.as_bytes())?;
             println!(
-                "Plant needs watering: {} ({} days since last watered)",
-                &plant, days
+                "Created profile {}, config at {}",
+                args.name,
+                config.display()
             );
+            if args.default {
+                profiles.set_default_profile(&args.name)?;
+            }
+        }
+        ProfileCommand::Delete(args) => {
+            if !profiles.exists(&args.name)? {
+                bail!("no profile named {}", args.name)
+            }
+            let prompt = format!("Delete profile {} with its config and history?", args.name);
+            if !args.yes && !confirm(&prompt)? {
+                println!("Nothing changed");
+                return Ok(());
+            }
+            profiles.delete(&args.name)?;
+            println!("Deleted profile {}", args.name);
         }
+        ProfileCommand::Default(args) => match args.name {
+            Some(name) => {
+                if !profiles.exists(&name)? {
+                    bail!("no profile named {name}")
+                }
+                profiles.set_default_profile(&name)?;
+                println!("Default profile is now {name}");
+            }
+            None => println!("{}", profiles.default_profile()?),
+        },
     }
     Ok(())
 }
 
+/// Print completions for a command line, one per line.  Errors are ignored, since there
+/// is nowhere useful to show them.
+fn cmd_complete(args: CompleteArgs) -> Result<()> {
+    let (Some(value_name), path_args) = complete::position(Cli::command(), &args.words) else {
+        return Ok(());
+    };
+    let profiles = Profiles::from_project_dirs()?;
+    let config = Paths::from_env(&path_args, &profiles)
+        .ok()
+        .filter(|paths| paths.config.exists())
+        .and_then(|paths| read_toml::<Config, _>(paths.config).ok());
+    let prefix = args.words.last().map_or("", String::as_str);
+    for candidate in complete::candidates(&value_name, prefix, config.as_ref(), &profiles) {
+        println!("{candidate}");
+    }
+    Ok(())
+}
+
+fn cmd_suggest_intervals(paths: &Paths, format: Format, args: SuggestArgs) -> Result<()> {
+    let _lock = args.apply.then(|| lock(paths)).transpose()?;
+    let config = load_config(paths)?;
+    let mut state = load_state(paths)?;
+    sync_state_with_config(&config, &mut state);
+    let mut plants = Vec::new();
+    for name in &args.plants {
+        plants.push(config.resolve_plant(name)?);
+    }
+    if plants.is_empty() {
+        plants = config.plants.keys().map(String::as_str).collect();
+    }
+    plants.sort_unstable();
+    let mut suggestions = Vec::new();
+    for plant in plants {
+        for (task, interval) in &config.plants[plant].tasks {
+            if args.task.as_ref().is_some_and(|t| t != task) {
+                continue;
+            }
+            suggestions.push(adapt::suggest(
+                plant,
+                task,
+                interval,
+                &state.plants[plant],
+                &config.settings.adaptive,
+            ));
+        }
+    }
+    if args.apply {
+        let mut doc = load_config_document(paths)?;
+        apply_suggestions(&mut doc, &suggestions)?;
+        return write_changes(paths, &state, Some(&doc));
+    }
+    let records: Vec<_> = suggestions.iter().map(SuggestionRecord::new).collect();
+    print_records(format, &records, |records| {
+        let days = |d: Option<u64>| d.map(|d| format!("{d}d"));
+        let rows: Vec<_> = records
+            .iter()
+            .map(|r| {
+                let proposed = match (r.current_days, r.proposed_days) {
+                    (Some(current), Some(proposed)) if current == proposed => "keep".to_string(),
+                    (_, proposed) => days(proposed).unwrap_or_else(|| "-".to_string()),
+                };
+                let evidence = match r.since {
+                    Some(since) => format!(
+                        "{} since {}: {}",
+                        r.samples,
+                        since.format("%Y-%m-%d"),
+                        r.recent_intervals
+                    ),
+                    None => "no intervals recorded".to_string(),
+                };
+                vec![
+                    r.plant.clone(),
+                    r.task.clone(),
+                    days(r.current_days).unwrap_or_else(|| "seasonal".to_string()),
+                    r.observed_days
+                        .map(|d| format!("{d}d"))
+                        .unwrap_or_else(|| "-".to_string()),
+                    proposed,
+                    evidence,
+                ]
+            })
+            .collect();
+        print_table(
+            &[
+                "PLANT", "TASK", "CURRENT", "OBSERVED", "PROPOSED", "EVIDENCE",
+            ],
+            &rows,
+        );
+    })
+}
+
+fn cmd_export(paths: &Paths, cmd: ExportCommand) -> Result<()> {
+    match cmd {
+        ExportCommand::Ics(args) => {
+            let now = chrono::Local::now().naive_local();
+            let config = load_config(paths)?;
+            args.select.check(&config)?;
+            let mut state = load_state(paths)?;
+            sync_state_with_config(&config, &mut state);
+            let mut events = Vec::new();
+            for ts in task_statuses(&config, &state, now) {
+                if !args.select.matches(&config.plants[&ts.plant]) {
+                    continue;
+                }
+                let description = match ts.last_done {
+                    Some(t) => format!(
+                        "Every {} days, last done {}.",
+                        ts.interval,
+                        t.format("%Y-%m-%d")
+                    ),
+                    None => format!("Every {} days, not done yet.", ts.interval),
+                };
+                let dates = due::upcoming(&config, &ts, now).take(args.count);
+                for (n, date) in dates.enumerate() {
+                    let n = (n + 1).to_string();
+                    events.push(ics::Event {
+                        uid: ics::uid(&[&paths.profile, &ts.plant, &ts.task, &n]),
+                        date,
+                        summary: format!("{} {}", ts.task, ts.plant),
+                        description: description.clone(),
+                        location: ts.room.clone(),
+                    });
+                }
+            }
+            events.sort_by(|a, b| (a.date, &a.summary).cmp(&(b.date, &b.summary)));
+            let name = match paths.profile.as_str() {
+                profiles::DEFAULT_PROFILE => "Plant care".to_string(),
+                profile => format!("Plant care ({profile})"),
+            };
+            let calendar = ics::calendar(&name, &events, chrono::Utc::now());
+            match args.output {
+                Some(path) => write_atomic(&path, calendar.as_bytes()),
+                None => {
+                    print!("{calendar}");
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+/// The tasks falling due on each day from today until `last` among the selected plants, in
+/// order of date.
+fn forecast_records(
+    config: &Config,
+    state: &State,
+    select: &Selection,
+    now: NaiveDateTime,
+    last: NaiveDate,
+) -> Vec<ForecastRecord> {
+    let today = now.date();
+    let mut records = Vec::new();
+    for ts in task_statuses(config, state, now) {
+        if !select.matches(&config.plants[&ts.plant]) {
+            continue;
+        }
+        let overdue = ts.days_remaining(today).is_some_and(|d| d < 0);
+        for (n, date) in due::upcoming(config, &ts, now)
+            .take_while(|d| *d <= last)
+            .enumerate()
+        {
+            records.push(ForecastRecord {
+                date,
+                plant: ts.plant.clone(),
+                room: ts.room.clone(),
+                task: ts.task.clone(),
+                overdue: n == 0 && overdue && date == today,
+            });
+        }
+    }
+    records.sort_by(|a, b| (a.date, &a.plant, &a.task).cmp(&(b.date, &b.plant, &b.task)));
+    records
+}
+
+fn cmd_forecast(paths: &Paths, format: Format, args: ForecastArgs) -> Result<()> {
+    let now = chrono::Local::now().naive_local();
+    let config = load_config(paths)?;
+    args.select.check(&config)?;
+    let mut state = load_state(paths)?;
+    sync_state_with_config(&config, &mut state);
+    let today = now.date();
+    let last = today
+        .checked_add_days(Days::new(args.days - 1))
+        .ok_or_else(|| anyhow!("cannot forecast {} days ahead", args.days))?;
+    let records = forecast_records(&config, &state, &args.select, now, last);
+    print_records(format, &records, |records| {
+        let rows: Vec<_> = today
+            .iter_days()
+            .take_while(|d| *d <= last)
+            .map(|day| {
+                let due: Vec<_> = records
+                    .iter()
+                    .filter(|r| r.date == day)
+                    .map(|r| match r.overdue {
+                        true => format!("{} {} (overdue)", r.task, r.plant),
+                        false => format!("{} {}", r.task, r.plant),
+                    })
+                    .collect();
+                vec![
+                    day.format("%a %Y-%m-%d").to_string(),
+                    due.len().to_string(),
+                    due.join(", "),
+                ]
+            })
+            .collect();
+        print_table(&["DAY", "DUE", "TASKS"], &rows);
+    })
+}
+
+fn cmd_stats(paths: &Paths, format: Format, args: StatsArgs) -> Result<()> {
+    let now = chrono::Local::now().naive_local();
+    let config = load_config(paths)?;
+    args.select.check(&config)?;
+    let mut state = load_state(paths)?;
+    sync_state_with_config(&config, &mut state);
+    let since = args
+        .since
+        .map(|s| timespec::parse_when(&s, now))
+        .transpose()?;
+    let until = match args.until {
+        Some(until) => timespec::parse_when(&until, now)?,
+        None => now,
+    };
+    let mut plants = Vec::new();
+    for name in &args.plants {
+        plants.push(config.resolve_plant(name)?);
+    }
+    if plants.is_empty() {
+        plants = config.plants.keys().map(String::as_str).collect();
+    }
+    plants.retain(|p| {
+        let plant = &config.plants[*p];
+        plant.tasks.contains_key(&args.task) && args.select.matches(plant)
+    });
+    plants.sort_unstable();
+    let stats: Vec<_> = plants
+        .iter()
+        .map(|p| stats::plant_stats(&config, p, &args.task, &state.plants[*p], since, until))
+        .collect();
+    let records: Vec<_> = stats.iter().map(StatsRecord::new).collect();
+    let overall = OverallRecord::new(&stats::Overall::new(&stats));
+    if format == Format::Json {
+        let report = StatsReport::new(&args.task, since, until, records, overall);
+        serde_json::to_writer_pretty(std::io::stdout().lock(), &report)?;
+        println!();
+        return Ok(());
+    }
+    print_records(format, &records, |records| {
+        let period = match since {
+            Some(since) => format!("{}", since.format("%Y-%m-%d")),
+            None => "the beginning".to_string(),
+        };
+        println!(
+            "{} from {period} to {}:",
+            args.task,
+            until.format("%Y-%m-%d")
+        );
+        let days = |d: Option<f64>| d.map_or("-".to_string(), |d| format!("{d}d"));
+        let rows: Vec<_> = records
+            .iter()
+            .map(|r| {
+                vec![
+                    r.plant.clone(),
+                    r.done.to_string(),
+                    format!("{}d", r.interval_days),
+                    days(r.mean_interval_days),
+                    days(r.median_interval_days),
+                    r.on_time_percent
+                        .map_or("-".to_string(), |p| format!("{p}%")),
+                    format!("{}d", r.longest_overdue_days.max(0)),
+                ]
+            })
+            .collect();
+        print_table(
+            &[
+                "PLANT",
+                "DONE",
+                "INTERVAL",
+                "MEAN",
+                "MEDIAN",
+                "ON TIME",
+                "LONGEST OVERDUE",
+            ],
+            &rows,
+        );
+        println!();
+        let on_time = overall
+            .on_time_percent
+            .map_or(String::new(), |p| format!(", {p}% on time"));
+        println!("Done {} times{on_time}", overall.done);
+        if let (Some(plant), Some(days)) =
+            (&overall.longest_overdue_plant, overall.longest_overdue_days)
+        {
+            println!("Longest overdue: {plant}, {days} days");
+        }
+        if !overall.most_neglected.is_empty() {
+            println!("Most neglected: {}", overall.most_neglected.join(", "));
+        }
+    })
+}
+
+fn read_input(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut input = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)
+            .context("failed to read stdin")?;
+        Ok(input)
+    } else {
+        std::fs::read_to_string(path).context_read(path)
+    }
+}
+
+fn cmd_import(paths: &Paths, args: ImportArgs) -> Result<()> {
+    let _lock = lock(paths)?;
+    let config = load_config(paths)?;
+    let mut doc = load_config_document(paths)?;
+    let mut state = load_state(paths)?;
+    sync_state_with_config(&config, &mut state);
+    let mut columns = import::Columns::default();
+    if let Some(column) = args.plant_column {
+        columns.plant = column;
+    }
+    if let Some(column) = args.date_column {
+        columns.date = column;
+    }
+    columns.task = args.task_column.or(columns.task);
+    columns.note = args.note_column.or(columns.note);
+    if !args.delimiter.is_ascii() {
+        bail!("the delimiter must be an ASCII character")
+    }
+    let format = import::CsvFormat {
+        columns,
+        delimiter: args.delimiter as u8,
+        date_format: args.date_format,
+        default_task: args.task,
+    };
+    let files = if args.files.is_empty() {
+        vec!["-".into()]
+    } else {
+        args.files
+    };
+    let mut rows = Vec::new();
+    for file in &files {
+        let input = read_input(file)?;
+        let parsed = import::parse_csv(&input, &format)
+            .with_context(|| format!("failed to import {}", file.display()))?;
+        rows.extend(parsed);
+    }
+    let report = import::merge(rows, &config, &mut state, args.create_plants);
+
+    for (plant, tasks) in &report.created {
+        validate_plant_name(plant)?;
+        let tasks: Vec<_> = tasks.iter().map(|t| (t.clone(), args.interval)).collect();
+        doc.add_plant(plant, &tasks);
+    }
+    let verb = if args.dry_run {
+        "Would import"
+    } else {
+        "Imported"
+    };
+    println!("{verb} {} events", report.total());
+    for (plant, added) in &report.added {
+        let created = match report.created.contains_key(plant) {
+            true => ", new plant",
+            false => "",
+        };
+        println!(
+            "  {plant}: {} ({} to {}{created})",
+            added.count,
+            added.first.format("%Y-%m-%d"),
+            added.last.format("%Y-%m-%d")
+        );
+    }
+    if report.duplicates > 0 {
+        println!(
+            "Skipped {} events already recorded on the same day",
+            report.duplicates
+        );
+    }
+    if !report.unknown_plants.is_empty() {
+        let plants: Vec<_> = report
+            .unknown_plants
+            .iter()
+            .map(|(plant, n)| format!("{plant} ({n})"))
+            .collect();
+        println!(
+            "Skipped rows for plants not in config, which --create-plants would add: {}",
+            plants.join(", ")
+        );
+    }
+    if !report.unknown_tasks.is_empty() {
+        let tasks: Vec<_> = report
+            .unknown_tasks
+            .iter()
+            .map(|((plant, task), n)| format!("{task} {plant} ({n})"))
+            .collect();
+        println!(
+            "Skipped rows for tasks the plants don't have: {}",
+            tasks.join(", ")
+        );
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+    doc.config()?;
+    write_changes(paths, &state, Some(&doc))
+}
+
+fn cmd_reading(paths: &Paths, format: Format, cmd: ReadingCommand) -> Result<()> {
+    match cmd {
+        ReadingCommand::Import(args) => {
+            let _lock = lock(paths)?;
+            let config = load_config(paths)?;
+            let mut state = load_state(paths)?;
+            sync_state_with_config(&config, &mut state);
+            let files = if args.files.is_empty() {
+                vec!["-".into()]
+            } else {
+                args.files
+            };
+            let mut readings = Vec::new();
+            for file in &files {
+                let input = read_input(file)?;
+                let parsed = readings::parse_readings(&input)
+                    .with_context(|| format!("failed to import {}", file.display()))?;
+                readings.extend(parsed);
+            }
+            let (mut added, mut unchanged) = (0, 0);
+            let mut unknown = std::collections::BTreeSet::new();
+            for (plant, reading) in readings {
+                let Some(plant) = config.find_plant(&plant) else {
+                    unknown.insert(plant);
+                    continue;
+                };
+                if state.plants.get_mut(plant).unwrap().add_reading(reading) {
+                    added += 1;
+                } else {
+                    unchanged += 1;
+                }
+            }
+            write_state(paths, &state)?;
+            println!("Imported {added} readings ({unchanged} already recorded)");
+            if !unknown.is_empty() {
+                let unknown: Vec<_> = unknown.iter().map(String::as_str).collect();
+                eprintln!(
+                    "warning: skipped readings for plants not in config: {}",
+                    unknown.join(", ")
+                );
+            }
+            Ok(())
+        }
+        ReadingCommand::List(args) => {
+            let config = load_config(paths)?;
+            let plant = config.resolve_plant(&args.plant)?;
+            let mut state = load_state(paths)?;
+            sync_state_with_config(&config, &mut state);
+            let records: Vec<_> = state.plants[plant]
+                .readings
+                .iter()
+                .filter(|r| args.metric.as_ref().is_none_or(|m| *m == r.metric))
+                .map(|r| ReadingRecord::new(plant, r))
+                .collect();
+            print_records(format, &records, |records| {
+                if records.is_empty() {
+                    println!("No readings for {plant}");
+                }
+                for r in records {
+                    println!(
+                        "{}  {:<10} {}",
+                        r.at.format("%Y-%m-%d %H:%M"),
+                        r.metric,
+                        r.value
+                    );
+                }
+            })
+        }
+    }
+}
+
+fn format_interval(d: Duration) -> String {
+    let days = d.num_days();
+    let hours = d.num_hours() - days * 24;
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else {
+        format!("{hours}h")
+    }
+}
+
+fn cmd_history(paths: &Paths, format: Format, args: HistoryArgs) -> Result<()> {
+    let config = load_config(paths)?;
+    let plant = config.resolve_plant(&args.plant)?;
+    let mut state = load_state(paths)?;
+    sync_state_with_config(&config, &mut state);
+    let status = &state.plants[plant];
+    let records: Vec<_> = status
+        .history_with_intervals()
+        .map(|(event, interval)| EventRecord::new(plant, event, interval))
+        .collect();
+    print_records(format, &records, |records| {
+        if records.is_empty() {
+            println!("No history for {plant}");
+        }
+        for r in records {
+            let interval = r
+                .hours_since_previous
+                .map(|h| format_interval(Duration::hours(h)))
+                .unwrap_or_default();
+            print!(
+                "{}  {:<10} {:<7} {:>8}",
+                r.at.format("%Y-%m-%d %H:%M"),
+                r.task,
+                r.kind,
+                interval
+            );
+            if let Some(until) = r.until {
+                print!("  until {}", until.format("%Y-%m-%d %H:%M"));
+            }
+            match &r.note {
+                Some(note) => println!("  {note}"),
+                None => println!(),
+            }
+        }
+    })
+}
+
+/// Selects plants by room and tags.
+#[derive(Args)]
+struct Selection {
+    /// only plants in this room (repeatable, matching any)
+    #[clap(long = "room", value_name = "ROOM")]
+    rooms: Vec<String>,
+    /// only plants with this tag (repeatable, matching all)
+    #[clap(long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+}
+
+impl Selection {
+    fn is_empty(&self) -> bool {
+        self.rooms.is_empty() && self.tags.is_empty()
+    }
+
+    fn matches(&self, plant: &Plant) -> bool {
+        (self.rooms.is_empty() || self.rooms.iter().any(|r| plant.in_room(r)))
+            && self.tags.iter().all(|t| plant.has_tag(t))
+    }
+
+    /// Fail on rooms and tags which no plant has, as they are most likely typos.
+    fn check(&self, config: &Config) -> Result<()> {
+        for room in &self.rooms {
+            if !config.plants.values().any(|p| p.in_room(room)) {
+                bail!("no plant is in room {room}")
+            }
+        }
+        for tag in &self.tags {
+            if !config.plants.values().any(|p| p.has_tag(tag)) {
+                bail!("no plant has tag {tag}")
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Parser)]
-struct WaterArgs {
+struct DoArgs {
     /// plant names
+    #[clap(value_name = "PLANT", conflicts_with_all = ["rooms", "tags"])]
     plants: Vec<String>,
-    /// mark all plants as being watered, which needed to be watered.
+    /// mark the task as done for all plants which were due.
     #[clap(short = 'a')]
     all: bool,
+    #[clap(flatten)]
+    select: Selection,
+    /// attach a note to the event
+    #[clap(short = 'n', long)]
+    note: Option<String>,
+    /// when the task was done, e.g. "yesterday", "yesterday 18:00", "3 days ago" or
+    /// "2023-04-01 08:00" [default: now]
+    #[clap(long)]
+    at: Option<String>,
+    /// record the event even if it is older than the latest one
+    #[clap(short, long)]
+    force: bool,
+}
+
+#[derive(Parser)]
+struct TaskArgs {
+    /// care task, e.g. water, fertilize or mist
+    #[clap(value_name = "TASK")]
+    task: String,
+    #[clap(flatten)]
+    args: DoArgs,
+}
+
+#[derive(Parser)]
+struct SnoozeArgs {
+    /// plant name
+    #[clap(value_name = "PLANT")]
+    plant: String,
+    /// only snooze this task, rather than all of the plant's tasks
+    #[clap(short, long, value_name = "TASK")]
+    task: Option<String>,
+    /// how long to snooze for, e.g. 2d, 12h or 1w [default: 1d]
+    #[clap(long = "for", conflicts_with = "until")]
+    duration: Option<String>,
+    /// snooze until a date (YYYY-MM-DD) or date and time (YYYY-MM-DD HH:MM)
+    #[clap(long)]
+    until: Option<String>,
+    /// attach a note to the snooze
+    #[clap(short = 'n', long)]
+    note: Option<String>,
+}
+
+#[derive(Parser)]
+struct PlantAddArgs {
+    /// plant name
+    name: String,
+    /// watering interval in days
+    #[clap(short, long, value_parser = clap::value_parser!(u64).range(1..=schedule::MAX_DAYS))]
+    interval: Option<u64>,
+    /// another care task and its interval, e.g. fertilize=30 (repeatable)
+    #[clap(short, long = "task", value_name = "TASK=DAYS", value_parser = parse_task_interval)]
+    tasks: Vec<(String, u64)>,
+    /// room the plant is in
+    #[clap(long, value_name = "ROOM")]
+    room: Option<String>,
+    /// tag for grouping plants, e.g. succulents (repeatable)
+    #[clap(long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+}
+
+#[derive(Parser)]
+struct PlantNameArgs {
+    /// plant name
+    #[clap(value_name = "PLANT")]
+    name: String,
 }
 
 #[derive(Parser)]
+struct PlantRenameArgs {
+    /// current plant name
+    #[clap(value_name = "PLANT")]
+    old: String,
+    /// new plant name
+    new: String,
+}
+
+#[derive(Parser)]
+struct PlantEditArgs {
+    /// plant name
+    #[clap(value_name = "PLANT")]
+    name: String,
+    /// set the watering interval in days
+    #[clap(short, long, value_parser = clap::value_parser!(u64).range(1..=schedule::MAX_DAYS))]
+    interval: Option<u64>,
+    /// add or change a care task's interval, e.g. fertilize=30 (repeatable)
+    #[clap(short, long = "task", value_name = "TASK=DAYS", value_parser = parse_task_interval)]
+    tasks: Vec<(String, u64)>,
+    /// remove a care task (repeatable)
+    #[clap(long = "remove-task", value_name = "TASK")]
+    remove_tasks: Vec<String>,
+    /// move the plant to a room, or out of any room with --room ""
+    #[clap(long, value_name = "ROOM")]
+    room: Option<String>,
+    /// add a tag (repeatable)
+    #[clap(long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+    /// remove a tag (repeatable)
+    #[clap(long = "remove-tag", value_name = "TAG")]
+    remove_tags: Vec<String>,
+}
+
+#[derive(Subcommand)]
+enum PlantCommand {
+    /// adds a plant to the config
+    Add(PlantAddArgs),
+    /// removes a plant and its history
+    #[clap(alias = "rm")]
+    Remove(PlantNameArgs),
+    /// renames a plant, keeping its history
+    #[clap(alias = "mv")]
+    Rename(PlantRenameArgs),
+    /// changes a plant's care tasks and intervals
+    #[clap(alias = "set-interval")]
+    Edit(PlantEditArgs),
+}
+
+#[derive(Parser)]
+struct UndoArgs {
+    /// number of actions to undo
+    #[clap(default_value_t = 1)]
+    count: usize,
+    /// apply without asking for confirmation
+    #[clap(short, long)]
+    yes: bool,
+}
+
+#[derive(Parser)]
+struct IcsArgs {
+    /// number of upcoming occurrences of each task
+    #[clap(short = 'n', long, default_value_t = 5)]
+    count: usize,
+    /// file to write the calendar to [default: stdout]
+    #[clap(short, long, value_name = "FILE")]
+    output: Option<std::path::PathBuf>,
+    #[clap(flatten)]
+    select: Selection,
+}
+
+#[derive(Subcommand)]
+enum ExportCommand {
+    /// writes upcoming care as all-day events in an iCalendar file, which calendars update
+    /// in place when it is imported again
+    Ics(IcsArgs),
+}
+
+#[derive(Parser)]
+struct NagArgs {
+    /// send reminders through the notifiers in the config's [notify] section instead of
+    /// printing them, e.g. from cron
+    #[clap(long)]
+    notify: bool,
+    #[clap(flatten)]
+    select: Selection,
+}
+
+#[derive(Subcommand)]
+enum NotifyCommand {
+    /// sends a made-up reminder through each configured notifier
+    Test,
+}
+
+#[derive(Parser)]
+struct DaemonArgs {
+    /// also check as soon as the daemon starts
+    #[clap(long)]
+    now: bool,
+    #[clap(flatten)]
+    select: Selection,
+}
+
+#[derive(Parser)]
+struct SuggestArgs {
+    /// plant names [default: all plants]
+    #[clap(value_name = "PLANT")]
+    plants: Vec<String>,
+    /// only this task
+    #[clap(short, long, value_name = "TASK")]
+    task: Option<String>,
+    /// change the intervals in the config as proposed
+    #[clap(long)]
+    apply: bool,
+}
+
+#[derive(Parser)]
+struct ReadingImportArgs {
+    /// CSV or JSON files of readings, or - for stdin [default: stdin]
+    #[clap(value_name = "FILE")]
+    files: Vec<std::path::PathBuf>,
+}
+
+#[derive(Parser)]
+struct ForecastArgs {
+    /// number of days to forecast, starting today
+    #[clap(short, long, default_value_t = 14, value_parser = clap::value_parser!(u64).range(1..))]
+    days: u64,
+    #[clap(flatten)]
+    select: Selection,
+}
+
+#[derive(Parser)]
+struct StatsArgs {
+    /// plant names [default: all plants]
+    #[clap(value_name = "PLANT")]
+    plants: Vec<String>,
+    /// the task to report on
+    #[clap(short, long, value_name = "TASK", default_value = WATER)]
+    task: String,
+    /// start of the period, e.g. 2023-01-01 or "90 days ago" [default: all history]
+    #[clap(long, value_name = "WHEN")]
+    since: Option<String>,
+    /// end of the period [default: now]
+    #[clap(long, value_name = "WHEN")]
+    until: Option<String>,
+    #[clap(flatten)]
+    select: Selection,
+}
+
+#[derive(Parser)]
+struct ImportArgs {
+    /// CSV files of care history, or - for stdin [default: stdin]
+    #[clap(value_name = "FILE")]
+    files: Vec<std::path::PathBuf>,
+    /// column holding plant names [default: plant]
+    #[clap(long, value_name = "COLUMN")]
+    plant_column: Option<String>,
+    /// column holding when tasks were done [default: date]
+    #[clap(long, value_name = "COLUMN")]
+    date_column: Option<String>,
+    /// column holding task names [default: task, if there is one]
+    #[clap(long, value_name = "COLUMN")]
+    task_column: Option<String>,
+    /// column holding notes [default: note, if there is one]
+    #[clap(long, value_name = "COLUMN")]
+    note_column: Option<String>,
+    /// strftime format of dates, e.g. %d/%m/%Y [default: YYYY-MM-DD with an optional time,
+    /// or RFC 3339]
+    #[clap(long, value_name = "FORMAT")]
+    date_format: Option<String>,
+    /// field separator
+    #[clap(long, default_value_t = ',')]
+    delimiter: char,
+    /// task for rows when there is no task column
+    #[clap(long, value_name = "TASK", default_value = WATER)]
+    task: String,
+    /// add plants which are not in the config, instead of skipping their rows
+    #[clap(long)]
+    create_plants: bool,
+    /// interval in days of the tasks of added plants
+    #[clap(long, value_name = "DAYS", default_value_t = 7, value_parser = clap::value_parser!(u64).range(1..=schedule::MAX_DAYS))]
+    interval: u64,
+    /// show what would be imported without changing anything
+    #[clap(short = 'n', long)]
+    dry_run: bool,
+}
+
+#[derive(Parser)]
+struct ReadingListArgs {
+    /// plant name
+    #[clap(value_name = "PLANT")]
+    plant: String,
+    /// only readings of this metric, e.g. moisture
+    #[clap(short, long)]
+    metric: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum ReadingCommand {
+    /// records sensor readings from CSV or JSON, with timestamp, plant, metric and value
+    /// fields
+    Import(ReadingImportArgs),
+    /// lists a plant's sensor readings
+    #[clap(alias = "ls")]
+    List(ReadingListArgs),
+}
+
+#[derive(Parser)]
+struct ProfileCreateArgs {
+    /// profile name
+    name: String,
+    /// also make this the default profile
+    #[clap(long)]
+    default: bool,
+}
+
+#[derive(Parser)]
+struct ProfileDeleteArgs {
+    /// profile name
+    #[clap(value_name = "PROFILE")]
+    name: String,
+    /// delete without asking for confirmation
+    #[clap(short, long)]
+    yes: bool,
+}
+
+#[derive(Parser)]
+struct ProfileDefaultArgs {
+    /// profile to use when --profile is not given; prints the current default if omitted
+    #[clap(value_name = "PROFILE")]
+    name: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// lists profiles, marking the default with *
+    #[clap(alias = "ls")]
+    List,
+    /// creates a profile with its own config and state
+    Create(ProfileCreateArgs),
+    /// deletes a profile's config and history
+    #[clap(alias = "rm")]
+    Delete(ProfileDeleteArgs),
+    /// shows or sets the default profile
+    Default(ProfileDefaultArgs),
+}
+
+#[derive(Parser)]
+struct HistoryArgs {
+    /// plant name
+    #[clap(value_name = "PLANT")]
+    plant: String,
+}
+
+#[derive(Parser)]
+struct CompletionsArgs {
+    /// shell to print a completion script for
+    #[clap(value_enum)]
+    shell: clap_complete::Shell,
+}
+
+#[derive(Parser)]
+struct CompleteArgs {
+    /// the command line being completed, ending with the word under the cursor
+    #[clap(last = true)]
+    words: Vec<String>,
+}
+
+#[derive(Subcommand)]
 enum Command {
-    /// nags you about unwatered houseplants
-    Nag,
+    /// nags you about houseplants which are due for care, grouped by room
+    Nag(NagArgs),
+    /// shows every plant's tasks, most urgent first
+    #[clap(alias = "list")]
+    Status(Selection),
     /// marks plants as being watered
-    Water(WaterArgs),
+    Water(DoArgs),
+    /// marks a care task as done for plants
+    Do(TaskArgs),
+    /// postpones reminders for a plant without marking it as cared for
+    Snooze(SnoozeArgs),
+    /// adds, removes, renames or edits plants
+    #[clap(subcommand)]
+    Plant(PlantCommand),
+    /// prints the config and state files in effect, and where they came from
+    Paths,
+    /// reverts the last recorded actions, after showing what will change
+    Undo(UndoArgs),
+    /// lists past events for a plant, with the interval between each
+    History(HistoryArgs),
+    /// records past care from CSV files, such as spreadsheets and other apps' exports
+    Import(ImportArgs),
+    /// imports and lists sensor readings, such as soil moisture
+    #[clap(subcommand)]
+    Reading(ReadingCommand),
+    /// shows which tasks fall due on each of the coming days, assuming each is done when due
+    Forecast(ForecastArgs),
+    /// reports how well plants have been kept up with: intervals, punctuality and neglect
+    Stats(StatsArgs),
+    /// compares intervals with how often tasks are actually done, and proposes new ones
+    SuggestIntervals(SuggestArgs),
+    /// keeps running, sending reminders at the times in settings.daemon until stopped
+    Daemon(DaemonArgs),
+    /// exports the care schedule for other programs
+    #[clap(subcommand)]
+    Export(ExportCommand),
+    /// checks the notifiers in the config's [notify] section
+    #[clap(subcommand)]
+    Notify(NotifyCommand),
+    /// manages profiles, each with its own plants and history
+    #[clap(subcommand)]
+    Profile(ProfileCommand),
+    /// prints a shell completion script, e.g. `source <(plant-paladin completions bash)`
+    Completions(CompletionsArgs),
+    /// lists completions for a command line; used by the completion scripts
+    #[clap(name = "complete-words", hide = true)]
+    Complete(CompleteArgs),
+}
+
+#[derive(Parser)]
+struct Cli {
+    /// output format for commands which list plants or events
+    #[clap(long, global = true, value_enum, default_value = "text")]
+    format: Format,
+    #[clap(flatten)]
+    paths: PathArgs,
+    #[clap(subcommand)]
+    command: Command,
 }
 
 fn main() -> Result<()> {
-    let cmd = Command::parse();
-    let dirs = directories::ProjectDirs::from("", "", "plant-paladin")
-        .ok_or_else(|| anyhow!("unable to retrieve user home dir"))?;
-    if !dirs.config_dir().exists() {
-        std::fs::create_dir(dirs.config_dir())?;
+    let Cli {
+        format,
+        paths,
+        command,
+    } = Cli::parse();
+    match command {
+        Command::Profile(cmd) => return cmd_profile(cmd),
+        Command::Completions(args) => {
+            complete::print_script(args.shell, Cli::command());
+            return Ok(());
+        }
+        Command::Complete(args) => return cmd_complete(args),
+        _ => {}
     }
-    match cmd {
-        Command::Nag => cmd_nag(&dirs),
-        Command::Water(args) => cmd_water(&dirs, args),
+    let profiles = Profiles::from_project_dirs()?;
+    let paths = Paths::from_env(&paths, &profiles)?;
+    if paths.state_in_profile() {
+        if let Some((from, to)) = profiles.migrate_state(&paths.profile)? {
+            eprintln!(
+                "note: moved state and undo journal from {} to {}",
+                from.display(),
+                to.display()
+            );
+        }
+    }
+    if !matches!(command, Command::Paths) {
+        paths.create_dirs()?;
+    }
+    match command {
+        Command::Paths => cmd_paths(&paths),
+        Command::Nag(args) => cmd_nag(&paths, format, args),
+        Command::Status(select) => cmd_status(&paths, format, select),
+        Command::Water(args) => cmd_do(&paths, WATER, args),
+        Command::Do(TaskArgs { task, args }) => cmd_do(&paths, &task, args),
+        Command::Snooze(args) => cmd_snooze(&paths, args),
+        Command::Plant(cmd) => cmd_plant(&paths, cmd),
+        Command::Undo(args) => cmd_undo(&paths, args),
+        Command::History(args) => cmd_history(&paths, format, args),
+        Command::Import(args) => cmd_import(&paths, args),
+        Command::Stats(args) => cmd_stats(&paths, format, args),
+        Command::Forecast(args) => cmd_forecast(&paths, format, args),
+        Command::Reading(cmd) => cmd_reading(&paths, format, cmd),
+        Command::SuggestIntervals(args) => cmd_suggest_intervals(&paths, format, args),
+        Command::Daemon(args) => cmd_daemon(&paths, args),
+        Command::Notify(cmd) => cmd_notify(&paths, cmd),
+        Command::Export(cmd) => cmd_export(&paths, cmd),
+        Command::Profile(_) | Command::Completions(_) | Command::Complete(_) => unreachable!(),
     }
 }
 
@@ -194,4 +1746,132 @@ mod tests {
         let _: Config = toml::from_str(DEFAULT_CONFIG_TOML)?;
         Ok(())
     }
+
+    #[test]
+    fn forecast_marks_overdue_tasks() -> Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            [fern]
+            watering_interval = 7
+            room = "kitchen"
+            [cactus]
+            watering_interval = 21
+            "#,
+        )?;
+        let mut state = State::default();
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        let d = |s: &str| s.parse::<NaiveDate>().unwrap();
+        let fern = state.plants.entry("fern".to_string()).or_default();
+        fern.record(t("2023-04-01T08:00:00"), WATER, EventKind::Done, None);
+        let cactus = state.plants.entry("cactus".to_string()).or_default();
+        cactus.record(t("2023-04-09T08:00:00"), WATER, EventKind::Done, None);
+        let select = Selection {
+            rooms: Vec::new(),
+            tags: Vec::new(),
+        };
+
+        let now = t("2023-04-10T12:00:00");
+        let records = forecast_records(&config, &state, &select, now, d("2023-04-30"));
+        let summary: Vec<_> = records
+            .iter()
+            .map(|r| (r.date, r.plant.as_str(), r.overdue))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (d("2023-04-10"), "fern", true),
+                (d("2023-04-17"), "fern", false),
+                (d("2023-04-24"), "fern", false),
+                (d("2023-04-30"), "cactus", false),
+            ]
+        );
+        assert_eq!(records[0].room.as_deref(), Some("kitchen"));
+
+        let select = Selection {
+            rooms: vec!["kitchen".to_string()],
+            tags: Vec::new(),
+        };
+        let records = forecast_records(&config, &state, &select, now, d("2023-04-10"));
+        assert_eq!(records.len(), 1);
+        Ok(())
+    }
+
+    /// Paths to a config and state in a new directory named after `test`.
+    fn test_paths(test: &str) -> Result<Paths> {
+        let dir = std::env::temp_dir().join(format!("plant-paladin-{test}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Paths {
+            profile: "default".to_string(),
+            config: dir.join("config.toml"),
+            config_source: paths::Source::Flag("--config"),
+            state: dir.join("state.toml"),
+            state_source: paths::Source::Flag("--state"),
+        })
+    }
+
+    fn plant_command(args: &[&str]) -> PlantCommand {
+        let args = ["plant-paladin", "plant"].iter().chain(args);
+        match Cli::try_parse_from(args).unwrap().command {
+            Command::Plant(cmd) => cmd,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn plants_keep_a_task() -> Result<()> {
+        let paths = test_paths("tasks")?;
+        let original = "[fern]\nwatering_interval = 7\n";
+        std::fs::write(&paths.config, original)?;
+        let err = cmd_plant(&paths, plant_command(&["add", "aloe"])).unwrap_err();
+        assert!(err.to_string().starts_with("give aloe a task"), "{err}");
+        let err = cmd_plant(
+            &paths,
+            plant_command(&["edit", "fern", "--remove-task", "water"]),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().starts_with("cannot remove the last task"),
+            "{err}"
+        );
+        assert_eq!(std::fs::read_to_string(&paths.config)?, original);
+
+        cmd_plant(&paths, plant_command(&["add", "aloe", "-t", "mist=3"]))?;
+        cmd_plant(
+            &paths,
+            plant_command(&["edit", "aloe", "-i", "14", "--remove-task", "mist"]),
+        )?;
+        let config = load_config(&paths)?;
+        assert_eq!(
+            config.plants["aloe"].tasks.keys().collect::<Vec<_>>(),
+            [WATER]
+        );
+        std::fs::remove_dir_all(paths.config.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn undo_restores_config() -> Result<()> {
+        let paths = test_paths("undo")?;
+        let original = "# ferns like it damp\n[fern]\nwatering_interval = 7\n";
+        std::fs::write(&paths.config, original)?;
+        let rename = PlantRenameArgs {
+            old: "fern".to_string(),
+            new: "boston".to_string(),
+        };
+        cmd_plant(&paths, PlantCommand::Rename(rename))?;
+        assert!(load_config(&paths)?.plants.contains_key("boston"));
+
+        cmd_undo(
+            &paths,
+            UndoArgs {
+                count: 1,
+                yes: true,
+            },
+        )?;
+        assert_eq!(std::fs::read_to_string(&paths.config)?, original);
+        assert!(!load_state(&paths)?.plants.contains_key("boston"));
+        assert!(load_journal(&paths)?.entries.is_empty());
+        std::fs::remove_dir_all(paths.config.parent().unwrap())?;
+        Ok(())
+    }
 }
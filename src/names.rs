@@ -0,0 +1,91 @@
+/// The result of looking a name up among candidates.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lookup<'a> {
+    Found(&'a str),
+    /// The name is a prefix of more than one candidate.
+    Ambiguous(Vec<&'a str>),
+    /// Nothing matched; holds the closest candidates, closest first.
+    Missing(Vec<&'a str>),
+}
+
+/// Number of single-character insertions, deletions and substitutions needed to turn `a`
+/// into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Look `name` up ignoring case, accepting an exact match or else an unambiguous prefix.
+pub fn lookup<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Lookup<'a> {
+    let name = name.to_lowercase();
+    let mut candidates: Vec<&str> = candidates.into_iter().collect();
+    candidates.sort_unstable();
+    if let Some(exact) = candidates.iter().find(|c| c.to_lowercase() == name) {
+        return Lookup::Found(exact);
+    }
+    let prefixed: Vec<&str> = candidates
+        .iter()
+        .copied()
+        .filter(|c| c.to_lowercase().starts_with(&name))
+        .collect();
+    match prefixed[..] {
+        [only] => return Lookup::Found(only),
+        [_, _, ..] => return Lookup::Ambiguous(prefixed),
+        [] => {}
+    }
+    let limit = (name.chars().count() / 3).max(2);
+    let mut close: Vec<(usize, &str)> = candidates
+        .iter()
+        .map(|c| (edit_distance(&name, &c.to_lowercase()), *c))
+        .filter(|(d, _)| *d <= limit)
+        .collect();
+    close.sort();
+    Lookup::Missing(close.into_iter().take(3).map(|(_, c)| c).collect())
+}
+
+/// Join names as "a, b or c".
+pub fn or_list(names: &[&str]) -> String {
+    match names {
+        [] => String::new(),
+        [only] => only.to_string(),
+        [init @ .., last] => format!("{} or {last}", init.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("fern", "fern"), 0);
+        assert_eq!(edit_distance("fren", "fern"), 2);
+        assert_eq!(edit_distance("monstera", "monstra"), 1);
+        assert_eq!(edit_distance("", "aloe"), 4);
+    }
+
+    #[test]
+    fn lookups() {
+        let plants = ["Boston Fern", "fern", "ficus", "monstera"];
+        assert_eq!(lookup("FERN", plants), Lookup::Found("fern"));
+        assert_eq!(lookup("bos", plants), Lookup::Found("Boston Fern"));
+        assert_eq!(
+            lookup("f", plants),
+            Lookup::Ambiguous(vec!["fern", "ficus"])
+        );
+        assert_eq!(lookup("fren", plants), Lookup::Missing(vec!["fern"]));
+        assert_eq!(lookup("monstra", plants), Lookup::Missing(vec!["monstera"]));
+        assert_eq!(lookup("cactus", plants), Lookup::Missing(vec![]));
+        assert_eq!(or_list(&["a", "b", "c"]), "a, b or c");
+    }
+}
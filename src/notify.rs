@@ -0,0 +1,421 @@
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+
+use crate::config::WATER;
+use crate::output::TaskRecord;
+
+/// How long to wait for a mail server or webhook before giving up.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Environment variable holding the SMTP password, if the config does not.
+pub const PASSWORD_ENV: &str = "PLANT_PALADIN_SMTP_PASSWORD";
+
+/// The `[notify]` section of the config: where reminders are sent, besides the terminal.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifySettings {
+    /// Show desktop notifications.
+    pub desktop: bool,
+    pub email: Option<EmailSettings>,
+    pub webhook: Option<WebhookSettings>,
+    /// A shell command run once for each due task, with details in `PLANT_PALADIN_*`
+    /// environment variables.
+    pub command: Option<String>,
+}
+
+impl NotifySettings {
+    pub fn is_empty(&self) -> bool {
+        !self.desktop && self.email.is_none() && self.webhook.is_none() && self.command.is_none()
+    }
+}
+
+/// How the connection to the mail server is secured.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    /// Plain text, only sensible for a server on the same machine.
+    None,
+    #[default]
+    Starttls,
+    Tls,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailSettings {
+    pub server: String,
+    /// Defaults to 587 for `starttls`, 465 for `tls` and 25 for `none`.
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: Security,
+    pub username: Option<String>,
+    /// Read from [`PASSWORD_ENV`] if not given.
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookSettings {
+    pub url: String,
+    /// Extra request headers, e.g. for authorisation.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+/// A reminder about the tasks which are due.  Webhooks receive it as JSON.
+#[derive(Clone, Debug, Serialize)]
+pub struct Notification {
+    pub title: String,
+    pub tasks: Vec<TaskRecord>,
+}
+
+impl Notification {
+    pub fn new(tasks: Vec<TaskRecord>) -> Self {
+        let title = match tasks.len() {
+            1 => "1 plant care task is due".to_string(),
+            n => format!("{n} plant care tasks are due"),
+        };
+        Notification { title, tasks }
+    }
+
+    /// A notification about a made-up plant, for checking that notifiers work.
+    pub fn test() -> Self {
+        let task = TaskRecord {
+            plant: "example".to_string(),
+            room: None,
+            task: WATER.to_string(),
+            interval_days: 7,
+            last_done: None,
+            days_since_done: None,
+            due: true,
+            snoozed_until: None,
+            moisture: None,
+            dry: false,
+            due_date: None,
+            days_until_due: None,
+            overdue_days: None,
+        };
+        Notification {
+            title: "plant-paladin test notification".to_string(),
+            tasks: vec![task],
+        }
+    }
+
+    /// One line per task, e.g. "water fern (never done)".
+    pub fn body(&self) -> String {
+        let lines: Vec<String> = self.tasks.iter().map(TaskRecord::summary).collect();
+        lines.join("\n")
+    }
+}
+
+/// Somewhere reminders can be sent.
+pub trait Notifier {
+    /// A short name for reporting which notifier failed.
+    fn name(&self) -> &str;
+    fn notify(&self, notification: &Notification) -> Result<()>;
+}
+
+/// The notifiers configured in `[notify]`.
+pub fn notifiers(settings: &NotifySettings) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+    if settings.desktop {
+        notifiers.push(Box::new(Desktop));
+    }
+    if let Some(email) = &settings.email {
+        notifiers.push(Box::new(Email(email.clone())));
+    }
+    if let Some(webhook) = &settings.webhook {
+        notifiers.push(Box::new(Webhook(webhook.clone())));
+    }
+    if let Some(command) = &settings.command {
+        notifiers.push(Box::new(Hook(command.clone())));
+    }
+    notifiers
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .status()
+        .with_context(|| format!("failed to run {program}"))?;
+    if !status.success() {
+        bail!("{program} failed with {status}")
+    }
+    Ok(())
+}
+
+/// Prints reminders to stdout.
+pub struct Terminal;
+
+impl Notifier for Terminal {
+    fn name(&self) -> &str {
+        "terminal"
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        println!("{}", notification.title);
+        for line in notification.body().lines() {
+            println!("  {line}");
+        }
+        Ok(())
+    }
+}
+
+/// Shows a desktop notification, with `notify-send` or, on macOS, `osascript`.
+pub struct Desktop;
+
+impl Notifier for Desktop {
+    fn name(&self) -> &str {
+        "desktop"
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        let (title, body) = (&notification.title, notification.body());
+        if cfg!(target_os = "macos") {
+            let script = format!("display notification {body:?} with title {title:?}");
+            run(Command::new("osascript").arg("-e").arg(script))
+        } else {
+            run(Command::new("notify-send")
+                .arg("--app-name=plant-paladin")
+                .arg(title)
+                .arg(body))
+        }
+    }
+}
+
+/// Sends an email over SMTP.
+pub struct Email(pub EmailSettings);
+
+impl Notifier for Email {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        let settings = &self.0;
+        let mut message = Message::builder()
+            .from(settings.from.parse().context("invalid from address")?)
+            .subject(&notification.title)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &settings.to {
+            message = message.to(to
+                .parse()
+                .with_context(|| format!("invalid to address {to:?}"))?);
+        }
+        let message = message.body(notification.body())?;
+
+        let server = settings.server.as_str();
+        let mut transport = match settings.security {
+            Security::None => SmtpTransport::builder_dangerous(server),
+            Security::Starttls => SmtpTransport::starttls_relay(server)?,
+            Security::Tls => SmtpTransport::relay(server)?,
+        }
+        .timeout(Some(TIMEOUT));
+        if let Some(port) = settings.port {
+            transport = transport.port(port);
+        }
+        if let Some(username) = &settings.username {
+            let password = match &settings.password {
+                Some(password) => password.clone(),
+                None => std::env::var(PASSWORD_ENV)
+                    .with_context(|| format!("no password in config or {PASSWORD_ENV}"))?,
+            };
+            transport = transport.credentials(Credentials::new(username.clone(), password));
+        }
+        transport
+            .build()
+            .send(&message)
+            .with_context(|| format!("failed to send email through {server}"))?;
+        Ok(())
+    }
+}
+
+/// POSTs the notification as JSON.
+pub struct Webhook(pub WebhookSettings);
+
+impl Notifier for Webhook {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        let mut request = ureq::post(&self.0.url).timeout(TIMEOUT);
+        for (name, value) in &self.0.headers {
+            request = request.set(name, value);
+        }
+        request.send_json(notification).context("failed to POST")?;
+        Ok(())
+    }
+}
+
+/// Runs a shell command for each task.
+pub struct Hook(pub String);
+
+impl Hook {
+    fn env(task: &TaskRecord) -> Vec<(&'static str, String)> {
+        let optional = |v: Option<String>| v.unwrap_or_default();
+        vec![
+            ("PLANT_PALADIN_PLANT", task.plant.clone()),
+            ("PLANT_PALADIN_ROOM", optional(task.room.clone())),
+            ("PLANT_PALADIN_TASK", task.task.clone()),
+            ("PLANT_PALADIN_SUMMARY", task.summary()),
+            (
+                "PLANT_PALADIN_DAYS_SINCE_DONE",
+                optional(task.days_since_done.map(|d| d.to_string())),
+            ),
+            (
+                "PLANT_PALADIN_OVERDUE_DAYS",
+                optional(task.overdue_days.map(|d| d.to_string())),
+            ),
+            (
+                "PLANT_PALADIN_MOISTURE",
+                optional(task.moisture.map(|m| m.to_string())),
+            ),
+            ("PLANT_PALADIN_DRY", task.dry.to_string()),
+        ]
+    }
+}
+
+impl Notifier for Hook {
+    fn name(&self) -> &str {
+        "command"
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<()> {
+        for task in &notification.tasks {
+            run(Command::new("sh")
+                .arg("-c")
+                .arg(&self.0)
+                .env("PLANT_PALADIN_TITLE", &notification.title)
+                .envs(Hook::env(task)))
+            .with_context(|| format!("command for {} {}", task.task, task.plant))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    /// Accept one connection on a local port, handling it on another thread.
+    fn serve<T: Send + 'static>(
+        handle: impl FnOnce(BufReader<TcpStream>) -> T + Send + 'static,
+    ) -> (u16, thread::JoinHandle<T>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle(BufReader::new(stream))
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn webhook_posts_json() -> Result<()> {
+        let (port, server) = serve(|mut conn| {
+            let mut content_length = 0;
+            let mut line = String::new();
+            while conn.read_line(&mut line).unwrap() > 2 {
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                line.clear();
+            }
+            let mut body = vec![0; content_length];
+            conn.read_exact(&mut body).unwrap();
+            conn.get_mut()
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            body
+        });
+        let webhook = Webhook(WebhookSettings {
+            url: format!("http://127.0.0.1:{port}/hook"),
+            headers: BTreeMap::new(),
+        });
+        webhook.notify(&Notification::test())?;
+        let body: serde_json::Value = serde_json::from_slice(&server.join().unwrap())?;
+        assert_eq!(body["title"], "plant-paladin test notification");
+        assert_eq!(body["tasks"][0]["plant"], "example");
+        Ok(())
+    }
+
+    #[test]
+    fn email_over_smtp() -> Result<()> {
+        let (port, server) = serve(|mut conn| {
+            let reply = |conn: &mut BufReader<TcpStream>, s: &str| {
+                conn.get_mut().write_all(s.as_bytes()).unwrap();
+            };
+            reply(&mut conn, "220 localhost ESMTP\r\n");
+            let (mut data, mut in_data) = (String::new(), false);
+            let mut line = String::new();
+            while conn.read_line(&mut line).unwrap() > 0 {
+                if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        reply(&mut conn, "250 queued\r\n");
+                    } else {
+                        data.push_str(&line);
+                    }
+                } else if line.starts_with("DATA") {
+                    in_data = true;
+                    reply(&mut conn, "354 go ahead\r\n");
+                } else if line.starts_with("QUIT") {
+                    reply(&mut conn, "221 bye\r\n");
+                    break;
+                } else {
+                    reply(&mut conn, "250 ok\r\n");
+                }
+                line.clear();
+            }
+            data
+        });
+        let email = Email(EmailSettings {
+            server: "127.0.0.1".to_string(),
+            port: Some(port),
+            security: Security::None,
+            username: None,
+            password: None,
+            from: "Plant Paladin <paladin@example.com>".to_string(),
+            to: vec!["me@example.com".to_string()],
+        });
+        email.notify(&Notification::test())?;
+        let data = server.join().unwrap();
+        assert!(data.contains("Subject: plant-paladin test notification"));
+        assert!(data.contains("To: me@example.com"));
+        assert!(data.contains("water example (never done)"));
+        Ok(())
+    }
+
+    #[test]
+    fn command_gets_task_in_env() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("plant-paladin-hook-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let out = dir.join("out");
+        let hook = Hook(format!(
+            "echo \"$PLANT_PALADIN_TASK $PLANT_PALADIN_PLANT [$PLANT_PALADIN_ROOM]\" >> {}",
+            out.display()
+        ));
+        hook.notify(&Notification::test())?;
+        assert_eq!(std::fs::read_to_string(&out)?, "water example []\n");
+        assert!(Hook("exit 3".to_string())
+            .notify(&Notification::test())
+            .is_err());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
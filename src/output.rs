@@ -0,0 +1,463 @@
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::adapt::Suggestion;
+use crate::due::TaskStatus;
+use crate::state::{Event, EventKind, Reading};
+use crate::stats::{Overall, PlantStats};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+/// A row of structured output.  `FIELDS` lists the serialised field names in order, so
+/// CSV output has a header even when there are no rows.
+pub trait Record: Serialize {
+    const FIELDS: &'static [&'static str];
+}
+
+/// Print records as a JSON array or CSV, or hand them to `text` for human-readable output.
+pub fn print_records<R: Record>(
+    format: Format,
+    records: &[R],
+    text: impl FnOnce(&[R]),
+) -> Result<()> {
+    match format {
+        Format::Text => text(records),
+        Format::Json => {
+            serde_json::to_writer_pretty(std::io::stdout().lock(), records)?;
+            println!();
+        }
+        Format::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(std::io::stdout().lock());
+            writer.write_record(R::FIELDS)?;
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+pub fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let print_row = |cells: &mut dyn Iterator<Item = &str>| {
+        let line: Vec<_> = cells
+            .zip(&widths)
+            .map(|(cell, &w)| format!("{cell:<w$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(&mut header.iter().copied());
+    for row in rows {
+        print_row(&mut row.iter().map(String::as_str));
+    }
+}
+
+fn whole_seconds(t: NaiveDateTime) -> NaiveDateTime {
+    t.with_nanosecond(0).unwrap()
+}
+
+/// A plant's care task as reported by `nag` and `status`.
+#[derive(Clone, Debug, Serialize)]
+pub struct TaskRecord {
+    pub plant: String,
+    pub room: Option<String>,
+    pub task: String,
+    /// Interval in days currently in effect.
+    pub interval_days: u64,
+    /// `null` if the task has never been done.
+    pub last_done: Option<NaiveDateTime>,
+    pub days_since_done: Option<i64>,
+    /// Whether the task is due now.  Snoozed tasks are never due.
+    pub due: bool,
+    /// End of the current snooze, if any.
+    pub snoozed_until: Option<NaiveDateTime>,
+    /// Latest moisture reading since the plant was last watered, for watering.
+    pub moisture: Option<f64>,
+    /// Whether `moisture` is below the plant's threshold, which makes watering due.
+    pub dry: bool,
+    /// `null` if the task has never been done, in which case it is due immediately.
+    pub due_date: Option<NaiveDate>,
+    /// Negative once the task is overdue.
+    pub days_until_due: Option<i64>,
+    /// Zero unless the task is overdue.
+    pub overdue_days: Option<i64>,
+}
+
+impl Record for TaskRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "plant",
+        "room",
+        "task",
+        "interval_days",
+        "last_done",
+        "days_since_done",
+        "due",
+        "snoozed_until",
+        "moisture",
+        "dry",
+        "due_date",
+        "days_until_due",
+        "overdue_days",
+    ];
+}
+
+impl TaskRecord {
+    /// A short description of the task and why it is due, e.g. "water fern (never done)".
+    pub fn summary(&self) -> String {
+        let reason = match self.days_since_done {
+            _ if self.dry => format!("dry, moisture {}", self.moisture.unwrap_or_default()),
+            Some(days) => format!("{days} days since last done"),
+            None => "never done".to_string(),
+        };
+        format!("{} {} ({reason})", self.task, self.plant)
+    }
+
+    pub fn new(ts: &TaskStatus, now: NaiveDateTime) -> Self {
+        let days_until_due = ts.days_remaining(now.date());
+        TaskRecord {
+            plant: ts.plant.clone(),
+            room: ts.room.clone(),
+            task: ts.task.clone(),
+            interval_days: ts.interval,
+            last_done: ts.last_done.map(whole_seconds),
+            days_since_done: ts.days_since(now),
+            due: ts.is_due(now),
+            snoozed_until: ts.snoozed_until.map(whole_seconds),
+            moisture: ts.moisture,
+            dry: ts.is_dry(),
+            due_date: ts.next_due().map(|t| t.date()),
+            days_until_due,
+            overdue_days: days_until_due.map(|d| (-d).max(0)),
+        }
+    }
+}
+
+/// An entry in a plant's history, as reported by `history`.
+#[derive(Clone, Debug, Serialize)]
+pub struct EventRecord {
+    pub plant: String,
+    pub task: String,
+    pub kind: EventKind,
+    pub at: NaiveDateTime,
+    /// End of a snooze.
+    pub until: Option<NaiveDateTime>,
+    /// Time since the previous event of the same kind for the same task.
+    pub hours_since_previous: Option<i64>,
+    pub note: Option<String>,
+}
+
+impl Record for EventRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "plant",
+        "task",
+        "kind",
+        "at",
+        "until",
+        "hours_since_previous",
+        "note",
+    ];
+}
+
+impl EventRecord {
+    pub fn new(plant: &str, event: &Event, since_previous: Option<chrono::Duration>) -> Self {
+        EventRecord {
+            plant: plant.to_string(),
+            task: event.task.clone(),
+            kind: event.kind,
+            at: whole_seconds(event.at),
+            until: event.until.map(whole_seconds),
+            hours_since_previous: since_previous.map(|d| d.num_hours()),
+            note: event.note.clone(),
+        }
+    }
+}
+
+/// A sensor reading, as reported by `reading list`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReadingRecord {
+    pub plant: String,
+    pub metric: String,
+    pub at: NaiveDateTime,
+    pub value: f64,
+}
+
+impl Record for ReadingRecord {
+    const FIELDS: &'static [&'static str] = &["plant", "metric", "at", "value"];
+}
+
+impl ReadingRecord {
+    pub fn new(plant: &str, reading: &Reading) -> Self {
+        ReadingRecord {
+            plant: plant.to_string(),
+            metric: reading.metric.clone(),
+            at: whole_seconds(reading.at),
+            value: reading.value,
+        }
+    }
+}
+
+/// A comparison of a task's interval with its history, as reported by `suggest-intervals`.
+#[derive(Clone, Debug, Serialize)]
+pub struct SuggestionRecord {
+    pub plant: String,
+    pub task: String,
+    /// `null` if the interval is seasonal.
+    pub current_days: Option<u64>,
+    /// Median of the recent intervals, to a tenth of a day.
+    pub observed_days: Option<f64>,
+    /// `null` without enough history, or for seasonal intervals.
+    pub proposed_days: Option<u64>,
+    pub samples: usize,
+    /// When the first of the recent intervals began.
+    pub since: Option<NaiveDateTime>,
+    /// The recent intervals in days, oldest first, separated by spaces.
+    pub recent_intervals: String,
+}
+
+impl Record for SuggestionRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "plant",
+        "task",
+        "current_days",
+        "observed_days",
+        "proposed_days",
+        "samples",
+        "since",
+        "recent_intervals",
+    ];
+}
+
+fn tenths(days: f64) -> f64 {
+    (days * 10.0).round() / 10.0
+}
+
+impl SuggestionRecord {
+    pub fn new(s: &Suggestion) -> Self {
+        let recent: Vec<_> = s.intervals.iter().map(|d| tenths(*d).to_string()).collect();
+        SuggestionRecord {
+            plant: s.plant.clone(),
+            task: s.task.clone(),
+            current_days: s.current,
+            observed_days: s.observed.map(tenths),
+            proposed_days: s.proposed,
+            samples: s.intervals.len(),
+            since: s.since.map(whole_seconds),
+            recent_intervals: recent.join(" "),
+        }
+    }
+}
+
+/// A task falling due on a day, as reported by `forecast`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ForecastRecord {
+    pub date: NaiveDate,
+    pub plant: String,
+    pub room: Option<String>,
+    pub task: String,
+    /// Whether the task is already overdue, in which case `date` is today.
+    pub overdue: bool,
+}
+
+impl Record for ForecastRecord {
+    const FIELDS: &'static [&'static str] = &["date", "plant", "room", "task", "overdue"];
+}
+
+/// A plant's figures for a task over a period, as reported by `stats`.
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsRecord {
+    pub plant: String,
+    pub task: String,
+    pub done: usize,
+    /// Interval in effect at the end of the period.
+    pub interval_days: u64,
+    /// Between times done in the period and the time before, to a tenth of a day.
+    pub mean_interval_days: Option<f64>,
+    pub median_interval_days: Option<f64>,
+    pub on_time: usize,
+    pub late: usize,
+    /// `null` if the task was never done after having been done before.
+    pub on_time_percent: Option<f64>,
+    pub longest_overdue_days: i64,
+}
+
+impl Record for StatsRecord {
+    const FIELDS: &'static [&'static str] = &[
+        "plant",
+        "task",
+        "done",
+        "interval_days",
+        "mean_interval_days",
+        "median_interval_days",
+        "on_time",
+        "late",
+        "on_time_percent",
+        "longest_overdue_days",
+    ];
+}
+
+impl StatsRecord {
+    pub fn new(s: &PlantStats) -> Self {
+        StatsRecord {
+            plant: s.plant.clone(),
+            task: s.task.clone(),
+            done: s.done,
+            interval_days: s.interval,
+            mean_interval_days: s.mean_interval().map(tenths),
+            median_interval_days: s.median_interval().map(tenths),
+            on_time: s.on_time,
+            late: s.late,
+            on_time_percent: s.on_time_percent().map(tenths),
+            longest_overdue_days: s.longest_overdue,
+        }
+    }
+}
+
+/// All plants' figures together, as reported by `stats`.
+#[derive(Clone, Debug, Serialize)]
+pub struct OverallRecord {
+    pub done: usize,
+    pub on_time: usize,
+    pub late: usize,
+    pub on_time_percent: Option<f64>,
+    pub longest_overdue_plant: Option<String>,
+    pub longest_overdue_days: Option<i64>,
+    /// Worst first.
+    pub most_neglected: Vec<String>,
+}
+
+impl OverallRecord {
+    pub fn new(o: &Overall) -> Self {
+        OverallRecord {
+            done: o.done,
+            on_time: o.on_time,
+            late: o.late,
+            on_time_percent: o.on_time_percent().map(tenths),
+            longest_overdue_plant: o.longest_overdue.as_ref().map(|(p, _)| p.clone()),
+            longest_overdue_days: o.longest_overdue.as_ref().map(|(_, d)| *d),
+            most_neglected: o.most_neglected.clone(),
+        }
+    }
+}
+
+/// The JSON output of `stats`.
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsReport {
+    pub task: String,
+    /// `null` for all history.
+    pub since: Option<NaiveDateTime>,
+    pub until: NaiveDateTime,
+    pub plants: Vec<StatsRecord>,
+    pub overall: OverallRecord,
+}
+
+impl StatsReport {
+    pub fn new(
+        task: &str,
+        since: Option<NaiveDateTime>,
+        until: NaiveDateTime,
+        plants: Vec<StatsRecord>,
+        overall: OverallRecord,
+    ) -> Self {
+        StatsReport {
+            task: task.to_string(),
+            since: since.map(whole_seconds),
+            until: whole_seconds(until),
+            plants,
+            overall,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check `FIELDS` against the header csv takes from the struct, which is in the order
+    /// the values of each row are written.
+    fn assert_fields_match<R: Record>(record: &R) -> Result<()> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.serialize(record)?;
+        let csv = String::from_utf8(writer.into_inner()?)?;
+        let header: Vec<_> = csv.lines().next().unwrap().split(',').collect();
+        assert_eq!(header, R::FIELDS);
+        Ok(())
+    }
+
+    #[test]
+    fn fields_match_serialised_names() -> Result<()> {
+        assert_fields_match(&TaskRecord {
+            plant: "fern".to_string(),
+            room: None,
+            task: "water".to_string(),
+            interval_days: 7,
+            last_done: None,
+            days_since_done: None,
+            due: true,
+            snoozed_until: None,
+            moisture: None,
+            dry: false,
+            due_date: None,
+            days_until_due: None,
+            overdue_days: None,
+        })?;
+        assert_fields_match(&EventRecord {
+            plant: "fern".to_string(),
+            task: "water".to_string(),
+            kind: EventKind::Done,
+            at: "2023-04-01T09:30:00".parse()?,
+            until: None,
+            hours_since_previous: None,
+            note: None,
+        })?;
+        assert_fields_match(&ReadingRecord {
+            plant: "fern".to_string(),
+            metric: "moisture".to_string(),
+            at: "2023-04-01T09:30:00".parse()?,
+            value: 21.5,
+        })?;
+        assert_fields_match(&SuggestionRecord {
+            plant: "fern".to_string(),
+            task: "water".to_string(),
+            current_days: Some(7),
+            observed_days: Some(5.5),
+            proposed_days: Some(6),
+            samples: 4,
+            since: None,
+            recent_intervals: "5 6 5 6".to_string(),
+        })?;
+        assert_fields_match(&ForecastRecord {
+            date: "2023-04-01".parse()?,
+            plant: "fern".to_string(),
+            room: Some("kitchen".to_string()),
+            task: "water".to_string(),
+            overdue: false,
+        })?;
+        assert_fields_match(&StatsRecord {
+            plant: "fern".to_string(),
+            task: "water".to_string(),
+            done: 4,
+            interval_days: 7,
+            mean_interval_days: Some(7.8),
+            median_interval_days: Some(8.5),
+            on_time: 3,
+            late: 1,
+            on_time_percent: Some(75.0),
+            longest_overdue_days: 3,
+        })
+    }
+}
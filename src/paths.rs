@@ -0,0 +1,245 @@
+use std::{ffi::OsString, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+
+use crate::profiles::{Profiles, DEFAULT_PROFILE};
+
+pub const CONFIG_ENV: &str = "PLANT_PALADIN_CONFIG";
+pub const STATE_ENV: &str = "PLANT_PALADIN_STATE";
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct PathArgs {
+    /// config file to use (overrides --dir and $PLANT_PALADIN_CONFIG)
+    #[clap(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// state file to use (overrides --dir and $PLANT_PALADIN_STATE)
+    #[clap(long, global = true, value_name = "FILE")]
+    pub state: Option<PathBuf>,
+    /// directory holding config.toml and state.toml (overrides the environment)
+    #[clap(long, global = true, value_name = "DIR")]
+    pub dir: Option<PathBuf>,
+    /// profile to use instead of the default one (overrides the environment)
+    #[clap(long, global = true, value_name = "PROFILE")]
+    pub profile: Option<String>,
+}
+
+/// Where a path came from, in decreasing order of precedence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    Flag(&'static str),
+    Dir,
+    Profile(String),
+    Env(&'static str),
+    /// The default profile's directory.
+    Default(String),
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Flag(flag) => f.write_str(flag),
+            Source::Dir => f.write_str("--dir"),
+            Source::Profile(name) => write!(f, "--profile {name}"),
+            Source::Env(var) => write!(f, "${var}"),
+            Source::Default(name) if name == DEFAULT_PROFILE => f.write_str("default"),
+            Source::Default(name) => write!(f, "default profile {name}"),
+        }
+    }
+}
+
+/// The files used by a command.
+#[derive(Clone, Debug)]
+pub struct Paths {
+    /// The profile in effect, although flags and the environment may override its files.
+    pub profile: String,
+    pub config: PathBuf,
+    pub config_source: Source,
+    pub state: PathBuf,
+    pub state_source: Source,
+}
+
+impl Paths {
+    /// Resolve paths, in order of precedence, from `--config`/`--state`, `--dir`,
+    /// `--profile`, the environment and finally the default profile's directories.
+    pub fn resolve(
+        args: &PathArgs,
+        env: impl Fn(&str) -> Option<OsString>,
+        profiles: &Profiles,
+    ) -> Result<Paths> {
+        let profile = match &args.profile {
+            Some(name) => name.clone(),
+            None => profiles.default_profile()?,
+        };
+        if !profiles.exists(&profile)? {
+            bail!("no profile named {profile}, create it with `plant-paladin profile create {profile}`")
+        }
+        let pick = |flag: (&'static str, &Option<PathBuf>), var: &'static str, path: PathBuf| {
+            if let (name, Some(path)) = flag {
+                return (path.clone(), Source::Flag(name));
+            }
+            if let Some(dir) = &args.dir {
+                return (dir.join(path.file_name().unwrap()), Source::Dir);
+            }
+            if args.profile.is_some() {
+                return (path, Source::Profile(profile.clone()));
+            }
+            if let Some(path) = env(var).filter(|p| !p.is_empty()) {
+                return (PathBuf::from(path), Source::Env(var));
+            }
+            (path, Source::Default(profile.clone()))
+        };
+        let (config, config_source) = pick(
+            ("--config", &args.config),
+            CONFIG_ENV,
+            profiles.config_dir(&profile).join("config.toml"),
+        );
+        let (state, state_source) = pick(
+            ("--state", &args.state),
+            STATE_ENV,
+            profiles.state_dir(&profile).join("state.toml"),
+        );
+        Ok(Paths {
+            profile,
+            config,
+            config_source,
+            state,
+            state_source,
+        })
+    }
+
+    /// Resolve paths using the real environment and project directories.
+    pub fn from_env(args: &PathArgs, profiles: &Profiles) -> Result<Paths> {
+        Paths::resolve(args, |var| std::env::var_os(var), profiles)
+    }
+
+    /// Whether the state file is the profile's own, rather than one given explicitly.
+    pub fn state_in_profile(&self) -> bool {
+        matches!(self.state_source, Source::Profile(_) | Source::Default(_))
+    }
+
+    /// A file next to the state file, named after it: `state.toml` gives `state.<suffix>`.
+    fn beside_state(&self, suffix: &str) -> PathBuf {
+        let stem = self.state.file_stem().unwrap_or_default().to_string_lossy();
+        self.state.with_file_name(format!("{stem}.{suffix}"))
+    }
+
+    pub fn journal(&self) -> PathBuf {
+        self.beside_state("journal.toml")
+    }
+
+    pub fn lock(&self) -> PathBuf {
+        self.beside_state("lock")
+    }
+
+    /// Create the directories the config and state files live in.
+    pub fn create_dirs(&self) -> Result<()> {
+        for path in [&self.config, &self.state] {
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("failed to create {}", dir.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve_in(profiles: &Profiles, args: PathArgs, env: &[(&str, &str)]) -> Result<Paths> {
+        Paths::resolve(
+            &args,
+            |var| {
+                env.iter()
+                    .find(|(k, _)| *k == var)
+                    .map(|(_, v)| OsString::from(v))
+            },
+            profiles,
+        )
+    }
+
+    fn resolve(args: PathArgs, env: &[(&str, &str)]) -> Paths {
+        let profiles = Profiles {
+            config_root: PathBuf::from("/home/me/.config/plant-paladin"),
+            state_root: PathBuf::from("/home/me/.config/plant-paladin"),
+        };
+        resolve_in(&profiles, args, env).unwrap()
+    }
+
+    #[test]
+    fn defaults_to_project_dir() {
+        let paths = resolve(PathArgs::default(), &[]);
+        assert_eq!(
+            paths.config,
+            PathBuf::from("/home/me/.config/plant-paladin/config.toml")
+        );
+        assert_eq!(paths.state_source, Source::Default(DEFAULT_PROFILE.into()));
+        assert_eq!(
+            paths.journal(),
+            PathBuf::from("/home/me/.config/plant-paladin/state.journal.toml")
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        let env = [
+            (CONFIG_ENV, "/env/config.toml"),
+            (STATE_ENV, "/env/state.toml"),
+        ];
+        let paths = resolve(PathArgs::default(), &env);
+        assert_eq!(paths.config, PathBuf::from("/env/config.toml"));
+        assert_eq!(paths.config_source, Source::Env(CONFIG_ENV));
+
+        let args = PathArgs {
+            dir: Some("/scratch".into()),
+            ..Default::default()
+        };
+        let paths = resolve(args.clone(), &env);
+        assert_eq!(paths.config, PathBuf::from("/scratch/config.toml"));
+        assert_eq!(paths.state, PathBuf::from("/scratch/state.toml"));
+
+        let args = PathArgs {
+            state: Some("/sync/office.toml".into()),
+            ..args
+        };
+        let paths = resolve(args, &env);
+        assert_eq!(paths.config, PathBuf::from("/scratch/config.toml"));
+        assert_eq!(paths.state, PathBuf::from("/sync/office.toml"));
+        assert_eq!(paths.state_source, Source::Flag("--state"));
+        assert_eq!(paths.lock(), PathBuf::from("/sync/office.lock"));
+    }
+
+    #[test]
+    fn profiles() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("plant-paladin-paths-{}", std::process::id()));
+        let profiles = Profiles {
+            config_root: root.join("config"),
+            state_root: root.join("data"),
+        };
+        let office = PathArgs {
+            profile: Some("office".into()),
+            ..Default::default()
+        };
+        assert!(resolve_in(&profiles, office.clone(), &[]).is_err());
+
+        profiles.create("office")?;
+        let env = [(STATE_ENV, "/env/state.toml")];
+        let paths = resolve_in(&profiles, office, &env)?;
+        assert_eq!(
+            paths.config,
+            root.join("config/profiles/office/config.toml")
+        );
+        assert_eq!(paths.state, root.join("data/profiles/office/state.toml"));
+        assert_eq!(paths.state_source, Source::Profile("office".into()));
+
+        profiles.set_default_profile("office")?;
+        let paths = resolve_in(&profiles, PathArgs::default(), &env)?;
+        assert_eq!(paths.profile, "office");
+        assert_eq!(paths.config_source, Source::Default("office".into()));
+        assert_eq!(paths.state_source, Source::Env(STATE_ENV));
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
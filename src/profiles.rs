@@ -0,0 +1,328 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use posix_cli_utils::IoContext;
+use serde::{Deserialize, Serialize};
+
+use crate::fsutil::{write_atomic, Lock};
+
+/// The profile which lives directly in the project directories, as used before profiles
+/// existed.
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProfilesFile {
+    default: Option<String>,
+}
+
+/// The set of profiles, each of which has its own config and state.  The default profile
+/// uses the project directories themselves; others live under `profiles/<name>`.  State
+/// is kept apart from config, so that config directories can be kept in version control.
+#[derive(Clone, Debug)]
+pub struct Profiles {
+    pub config_root: PathBuf,
+    pub state_root: PathBuf,
+}
+
+/// Check that `name` is a plain directory name, so that a profile can never reach outside
+/// the profiles directory.
+pub fn validate_profile_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        bail!("invalid profile name {name:?}, use letters, digits, - and _")
+    }
+    Ok(())
+}
+
+impl Profiles {
+    /// Profiles in the platform's project directories, with state in the state directory
+    /// where the platform has one and the data directory otherwise.
+    pub fn from_project_dirs() -> Result<Profiles> {
+        let dirs = directories::ProjectDirs::from("", "", "plant-paladin")
+            .ok_or_else(|| anyhow!("unable to retrieve user home dir"))?;
+        Ok(Profiles {
+            config_root: dirs.config_dir().to_path_buf(),
+            state_root: dirs.state_dir().unwrap_or(dirs.data_dir()).to_path_buf(),
+        })
+    }
+
+    fn settings_path(&self) -> PathBuf {
+        self.config_root.join("profiles.toml")
+    }
+
+    pub fn config_dir(&self, name: &str) -> PathBuf {
+        if name == DEFAULT_PROFILE {
+            self.config_root.clone()
+        } else {
+            self.config_root.join("profiles").join(name)
+        }
+    }
+
+    pub fn state_dir(&self, name: &str) -> PathBuf {
+        if name == DEFAULT_PROFILE {
+            self.state_root.clone()
+        } else {
+            self.state_root.join("profiles").join(name)
+        }
+    }
+
+    /// Whether a profile exists.  Fails if `name` is not a valid profile name.
+    pub fn exists(&self, name: &str) -> Result<bool> {
+        validate_profile_name(name)?;
+        Ok(name == DEFAULT_PROFILE || self.config_dir(name).is_dir())
+    }
+
+    /// All profiles, sorted, including the default profile.
+    pub fn list(&self) -> Result<Vec<String>> {
+        let mut names = vec![DEFAULT_PROFILE.to_string()];
+        let dir = self.config_root.join("profiles");
+        if dir.is_dir() {
+            for entry in std::fs::read_dir(&dir).context_read(&dir)? {
+                let entry = entry.context_read(&dir)?;
+                if entry.path().is_dir() {
+                    names.push(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// The profile used when none is given on the command line.
+    pub fn default_profile(&self) -> Result<String> {
+        let path = self.settings_path();
+        if !path.exists() {
+            return Ok(DEFAULT_PROFILE.to_string());
+        }
+        let contents = std::fs::read_to_string(&path).context_read(&path)?;
+        let file: ProfilesFile = toml::from_str(&contents)
+            .with_context(|| format!("failed to deserialise {}", path.display()))?;
+        Ok(file.default.unwrap_or_else(|| DEFAULT_PROFILE.to_string()))
+    }
+
+    pub fn set_default_profile(&self, name: &str) -> Result<()> {
+        validate_profile_name(name)?;
+        let file = ProfilesFile {
+            default: Some(name.to_string()),
+        };
+        std::fs::create_dir_all(&self.config_root).context_write(&self.config_root)?;
+        write_atomic(&self.settings_path(), toml::to_string(&file)?.as_bytes())
+    }
+
+    /// Move a profile's state and journal out of its config directory, where earlier
+    /// versions kept them.  Returns the directories moved from and to, if anything moved.
+    pub fn migrate_state(&self, name: &str) -> Result<Option<(PathBuf, PathBuf)>> {
+        let (old_dir, new_dir) = (self.config_dir(name), self.state_dir(name));
+        rename_legacy_journal(&old_dir)?;
+        let old_state = old_dir.join("state.toml");
+        if old_dir == new_dir || !old_state.exists() {
+            return Ok(None);
+        }
+        std::fs::create_dir_all(&new_dir).context_write(&new_dir)?;
+        let _lock = Lock::acquire(&new_dir.join("state.lock"))?;
+        if !old_state.exists() {
+            // Another process migrated it while we waited for the lock.
+            return Ok(None);
+        }
+        if new_dir.join("state.toml").exists() {
+            eprintln!(
+                "warning: ignoring old state at {}, using {}",
+                old_state.display(),
+                new_dir.join("state.toml").display()
+            );
+            return Ok(None);
+        }
+        // The journal first, so that an interrupted move is retried on the next run.
+        for file in ["state.journal.toml", "state.toml"] {
+            let old = old_dir.join(file);
+            if old.exists() {
+                move_file(&old, &new_dir.join(file))?;
+            }
+        }
+        let _ = std::fs::remove_file(old_dir.join("state.lock"));
+        Ok(Some((old_dir, new_dir)))
+    }
+
+    /// Create the directories for a new profile.
+    pub fn create(&self, name: &str) -> Result<()> {
+        if self.exists(name)? {
+            bail!("profile {name} already exists")
+        }
+        for dir in [self.config_dir(name), self.state_dir(name)] {
+            std::fs::create_dir_all(&dir).context_write(&dir)?;
+        }
+        Ok(())
+    }
+
+    /// Delete a profile's config and state.
+    pub fn delete(&self, name: &str) -> Result<()> {
+        if !self.exists(name)? {
+            bail!("no profile named {name}")
+        }
+        if name == DEFAULT_PROFILE {
+            bail!("the default profile cannot be deleted")
+        }
+        for dir in [self.config_dir(name), self.state_dir(name)] {
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir).context_write(&dir)?;
+            }
+        }
+        if self.default_profile()? == name {
+            self.set_default_profile(DEFAULT_PROFILE)?;
+        }
+        Ok(())
+    }
+}
+
+/// Give a journal the name it has had since it was kept beside the state file: versions
+/// before that named it `journal.toml`.
+fn rename_legacy_journal(dir: &Path) -> Result<()> {
+    let (old, new) = (dir.join("journal.toml"), dir.join("state.journal.toml"));
+    if !old.exists() || new.exists() {
+        return Ok(());
+    }
+    match std::fs::rename(&old, &new) {
+        // Another process renamed it first.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result.context_write(&old),
+    }
+}
+
+/// Rename a file, falling back to copying it when the destination is on another
+/// filesystem.
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    let contents = std::fs::read(from).context_read(from)?;
+    write_atomic(to, &contents)?;
+    std::fs::remove_file(from).context_write(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_list_delete() -> Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("plant-paladin-profiles-{}", std::process::id()));
+        let profiles = Profiles {
+            config_root: root.join("config"),
+            state_root: root.join("state"),
+        };
+        assert_eq!(profiles.list()?, [DEFAULT_PROFILE]);
+        assert_eq!(profiles.default_profile()?, DEFAULT_PROFILE);
+
+        profiles.create("office")?;
+        assert!(profiles.create("office").is_err());
+        assert!(profiles.create("../escape").is_err());
+        profiles.set_default_profile("office")?;
+        assert_eq!(profiles.list()?, [DEFAULT_PROFILE, "office"]);
+        assert_eq!(profiles.default_profile()?, "office");
+        assert_eq!(
+            profiles.state_dir("office"),
+            root.join("state/profiles/office")
+        );
+
+        profiles.delete("office")?;
+        assert_eq!(profiles.list()?, [DEFAULT_PROFILE]);
+        assert_eq!(profiles.default_profile()?, DEFAULT_PROFILE);
+        assert!(profiles.delete(DEFAULT_PROFILE).is_err());
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn names_stay_inside_profiles_dir() -> Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("plant-paladin-escape-{}", std::process::id()));
+        let profiles = Profiles {
+            config_root: root.join("config/plant-paladin"),
+            state_root: root.join("state/plant-paladin"),
+        };
+        profiles.create("office")?;
+        let config = profiles.config_root.join("config.toml");
+        let state = profiles.state_root.join("state.toml");
+        std::fs::write(&config, "")?;
+        std::fs::write(&state, "")?;
+        for name in ["..", "", ".", "../plant-paladin", "office/.."] {
+            assert!(profiles.exists(name).is_err(), "{name:?}");
+            assert!(profiles.delete(name).is_err(), "{name:?}");
+            assert!(profiles.set_default_profile(name).is_err(), "{name:?}");
+        }
+        assert!(config.exists() && state.exists());
+        assert!(profiles.exists("office")?);
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn migrates_state_out_of_config_dir() -> Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("plant-paladin-migrate-{}", std::process::id()));
+        let profiles = Profiles {
+            config_root: root.join("config"),
+            state_root: root.join("state"),
+        };
+        std::fs::create_dir_all(&profiles.config_root)?;
+        for file in [
+            "config.toml",
+            "state.toml",
+            "state.journal.toml",
+            "state.lock",
+        ] {
+            std::fs::write(profiles.config_root.join(file), file)?;
+        }
+        assert_eq!(
+            profiles.migrate_state(DEFAULT_PROFILE)?,
+            Some((root.join("config"), root.join("state")))
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("state/state.toml"))?,
+            "state.toml"
+        );
+        assert!(root.join("state/state.journal.toml").exists());
+        let left: Vec<_> = std::fs::read_dir(&profiles.config_root)?.collect();
+        assert_eq!(left.len(), 1);
+        assert_eq!(profiles.migrate_state(DEFAULT_PROFILE)?, None);
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn renames_legacy_journal() -> Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("plant-paladin-journal-{}", std::process::id()));
+        let profiles = Profiles {
+            config_root: root.join("config"),
+            state_root: root.join("state"),
+        };
+        std::fs::create_dir_all(&profiles.config_root)?;
+        for file in ["config.toml", "state.toml", "journal.toml"] {
+            std::fs::write(profiles.config_root.join(file), file)?;
+        }
+        profiles.migrate_state(DEFAULT_PROFILE)?;
+        assert_eq!(
+            std::fs::read_to_string(root.join("state/state.journal.toml"))?,
+            "journal.toml"
+        );
+        assert!(!root.join("config/journal.toml").exists());
+
+        // Where config and state share a directory, the journal is only renamed.
+        let shared = Profiles {
+            config_root: root.join("shared"),
+            state_root: root.join("shared"),
+        };
+        std::fs::create_dir_all(&shared.config_root)?;
+        std::fs::write(shared.config_root.join("journal.toml"), "journal.toml")?;
+        assert_eq!(shared.migrate_state(DEFAULT_PROFILE)?, None);
+        assert!(root.join("shared/state.journal.toml").exists());
+        assert!(!root.join("shared/journal.toml").exists());
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
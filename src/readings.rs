@@ -0,0 +1,114 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::Deserialize;
+
+use crate::config::MOISTURE;
+use crate::state::Reading;
+use crate::timespec;
+
+fn default_metric() -> String {
+    MOISTURE.to_string()
+}
+
+/// A timestamp as sensors log it: local time, RFC 3339 or seconds since the epoch.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Timestamp {
+    Epoch(i64),
+    Text(String),
+}
+
+impl Timestamp {
+    fn to_local(&self) -> Result<NaiveDateTime> {
+        match self {
+            Timestamp::Epoch(secs) => Local
+                .timestamp_opt(*secs, 0)
+                .single()
+                .map(|t| t.naive_local())
+                .ok_or_else(|| anyhow!("invalid timestamp {secs}")),
+            Timestamp::Text(s) => timespec::parse_timestamp(s),
+        }
+    }
+}
+
+/// A reading as it appears in an imported file.  The metric defaults to moisture.
+#[derive(Deserialize)]
+struct ReadingRow {
+    #[serde(alias = "at", alias = "time")]
+    timestamp: Timestamp,
+    plant: String,
+    #[serde(default = "default_metric")]
+    metric: String,
+    value: f64,
+}
+
+impl ReadingRow {
+    fn into_reading(self) -> Result<(String, Reading)> {
+        let reading = Reading {
+            at: self.timestamp.to_local()?,
+            metric: self.metric,
+            value: self.value,
+        };
+        Ok((self.plant, reading))
+    }
+}
+
+/// Parse readings, with the plant each is for, from CSV with a header row, a JSON array
+/// of objects or newline-delimited JSON objects.  Each has `timestamp` (or `at` or
+/// `time`), `plant`, `value` and optionally `metric` fields.
+pub fn parse_readings(input: &str) -> Result<Vec<(String, Reading)>> {
+    let rows: Vec<ReadingRow> = match input.trim_start().chars().next() {
+        Some('[') => serde_json::from_str(input).context("invalid JSON")?,
+        Some('{') => serde_json::Deserializer::from_str(input)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .context("invalid JSON")?,
+        _ => csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(input.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .context("invalid CSV")?,
+    };
+    rows.into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            row.into_reading()
+                .with_context(|| format!("reading {}", i + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_and_json() -> Result<()> {
+        let csv =
+            "timestamp, plant, value\n2023-04-01 08:00, fern, 31.5\n2023-04-01T09:00:00,fern,30\n";
+        let readings = parse_readings(csv)?;
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].0, "fern");
+        assert_eq!(readings[0].1.metric, MOISTURE);
+        assert_eq!(readings[1].1.at, "2023-04-01T09:00:00".parse()?);
+        assert_eq!(readings[1].1.value, 30.0);
+
+        let json =
+            r#"[{"at": "2023-04-01 08:00", "plant": "fern", "metric": "light", "value": 200}]"#;
+        let readings = parse_readings(json)?;
+        assert_eq!(readings[0].1.metric, "light");
+
+        let ndjson = format!(
+            "{{\"time\": {}, \"plant\": \"cactus\", \"value\": 5}}\n{{\"time\": \"2023-04-01T08:00:00+00:00\", \"plant\": \"fern\", \"value\": 6}}\n",
+            Local.with_ymd_and_hms(2023, 4, 1, 8, 0, 0).unwrap().timestamp()
+        );
+        let readings = parse_readings(&ndjson)?;
+        assert_eq!(readings[0].1.at, "2023-04-01T08:00:00".parse()?);
+        assert_eq!(readings[1].0, "fern");
+
+        let err = parse_readings("timestamp,plant,value\nyesterday,fern,3\n").unwrap_err();
+        assert_eq!(err.to_string(), "reading 1");
+        Ok(())
+    }
+}
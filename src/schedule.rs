@@ -0,0 +1,284 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use chrono::{Datelike, Month, NaiveDate};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The longest interval allowed, a century, which keeps due dates well within the range
+/// of dates.
+pub const MAX_DAYS: u64 = 36500;
+
+/// Check that an interval is at least a day and at most [`MAX_DAYS`].
+pub fn check_days(days: u64) -> anyhow::Result<u64> {
+    if !(1..=MAX_DAYS).contains(&days) {
+        bail!("intervals must be between 1 and {MAX_DAYS} days, not {days}")
+    }
+    Ok(days)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Hemisphere {
+    #[default]
+    North,
+    South,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    /// The (meteorological) season a month falls in.
+    pub fn of(month: u32, hemisphere: Hemisphere) -> Season {
+        let month = match hemisphere {
+            Hemisphere::North => month,
+            Hemisphere::South => (month + 5) % 12 + 1,
+        };
+        match month {
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            9..=11 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+}
+
+/// A month of the year (1-12), written in config files either as a number or a
+/// name such as `"jun"` or `"June"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MonthOfYear(u32);
+
+impl Serialize for MonthOfYear {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let month = Month::try_from(self.0 as u8).unwrap();
+        serializer.serialize_str(&month.name()[..3].to_ascii_lowercase())
+    }
+}
+
+impl<'de> Deserialize<'de> for MonthOfYear {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(u32),
+            Name(String),
+        }
+        let month = match Repr::deserialize(deserializer)? {
+            Repr::Number(n @ 1..=12) => n,
+            Repr::Number(n) => return Err(serde::de::Error::custom(format!("invalid month: {n}"))),
+            Repr::Name(name) => Month::from_str(&name)
+                .map_err(|_| serde::de::Error::custom(format!("invalid month: {name}")))?
+                .number_from_month(),
+        };
+        Ok(MonthOfYear(month))
+    }
+}
+
+/// An inclusive range of months, which may wrap around the end of the year.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonthRange {
+    pub from: MonthOfYear,
+    pub to: MonthOfYear,
+    pub days: u64,
+}
+
+impl MonthRange {
+    fn contains(&self, month: u32) -> bool {
+        let (from, to) = (self.from.0, self.to.0);
+        if from <= to {
+            (from..=to).contains(&month)
+        } else {
+            month >= from || month <= to
+        }
+    }
+}
+
+/// An interval which varies over the year.  Month ranges take precedence over
+/// seasons, which take precedence over the default.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(try_from = "ScheduleRepr")]
+pub struct Schedule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spring: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summer: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autumn: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub winter: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub months: Vec<MonthRange>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScheduleRepr {
+    default: Option<u64>,
+    spring: Option<u64>,
+    summer: Option<u64>,
+    autumn: Option<u64>,
+    winter: Option<u64>,
+    #[serde(default)]
+    months: Vec<MonthRange>,
+}
+
+impl TryFrom<ScheduleRepr> for Schedule {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: ScheduleRepr) -> anyhow::Result<Self> {
+        let schedule = Schedule {
+            default: repr.default,
+            spring: repr.spring,
+            summer: repr.summer,
+            autumn: repr.autumn,
+            winter: repr.winter,
+            months: repr.months,
+        };
+        let days = [
+            schedule.default,
+            schedule.spring,
+            schedule.summer,
+            schedule.autumn,
+            schedule.winter,
+        ];
+        for days in days
+            .into_iter()
+            .flatten()
+            .chain(schedule.months.iter().map(|r| r.days))
+        {
+            check_days(days)?;
+        }
+        // Every month must be covered, in either hemisphere.
+        for hemisphere in [Hemisphere::North, Hemisphere::South] {
+            for month in 1..=12 {
+                if schedule.days_in(month, hemisphere).is_none() {
+                    let name = Month::try_from(month as u8).unwrap().name();
+                    bail!("schedule has no interval for {name}, add a `default`")
+                }
+            }
+        }
+        Ok(schedule)
+    }
+}
+
+impl Schedule {
+    fn days_in(&self, month: u32, hemisphere: Hemisphere) -> Option<u64> {
+        if let Some(range) = self.months.iter().find(|r| r.contains(month)) {
+            return Some(range.days);
+        }
+        let seasonal = match Season::of(month, hemisphere) {
+            Season::Spring => self.spring,
+            Season::Summer => self.summer,
+            Season::Autumn => self.autumn,
+            Season::Winter => self.winter,
+        };
+        seasonal.or(self.default)
+    }
+}
+
+/// How often a task should be done: either a fixed number of days or a [`Schedule`].
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum Interval {
+    Days(u64),
+    Seasonal(Schedule),
+}
+
+impl<'de> Deserialize<'de> for Interval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Deserialise the schedule in two steps so validation errors aren't swallowed by
+        // the untagged enum.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Days(u64),
+            Seasonal(ScheduleRepr),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Days(days) => check_days(days)
+                .map(Interval::Days)
+                .map_err(serde::de::Error::custom),
+            Repr::Seasonal(repr) => Schedule::try_from(repr)
+                .map(Interval::Seasonal)
+                .map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl Interval {
+    /// The interval in days in effect on `date`.
+    pub fn days_on(&self, date: NaiveDate, hemisphere: Hemisphere) -> u64 {
+        match self {
+            Interval::Days(days) => *days,
+            Interval::Seasonal(schedule) => schedule.days_in(date.month(), hemisphere).unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn seasons_flip_between_hemispheres() {
+        assert_eq!(Season::of(7, Hemisphere::North), Season::Summer);
+        assert_eq!(Season::of(7, Hemisphere::South), Season::Winter);
+        assert_eq!(Season::of(12, Hemisphere::South), Season::Summer);
+        assert_eq!(Season::of(4, Hemisphere::South), Season::Autumn);
+        assert_eq!(Season::of(10, Hemisphere::South), Season::Spring);
+    }
+
+    #[test]
+    fn months_override_seasons() -> anyhow::Result<()> {
+        let interval: Interval = toml::from_str::<toml::Table>(
+            r#"
+            x = { default = 7, winter = 14, months = [{ from = "nov", to = 1, days = 21 }] }
+            "#,
+        )?["x"]
+            .clone()
+            .try_into()?;
+        let north = Hemisphere::North;
+        assert_eq!(interval.days_on(date("2023-07-01"), north), 7);
+        assert_eq!(interval.days_on(date("2023-02-01"), north), 14);
+        assert_eq!(interval.days_on(date("2023-12-01"), north), 21);
+        assert_eq!(interval.days_on(date("2023-11-30"), north), 21);
+        assert_eq!(interval.days_on(date("2023-07-01"), Hemisphere::South), 14);
+        Ok(())
+    }
+
+    #[test]
+    fn incomplete_schedule_is_rejected() {
+        let result = toml::from_str::<toml::Table>("x = { summer = 3, winter = 10 }").unwrap()["x"]
+            .clone()
+            .try_into::<Schedule>();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn out_of_range_intervals_are_rejected() {
+        for interval in [
+            "x = 0",
+            "x = 9999999999",
+            "x = { default = 36501 }",
+            "x = { default = 7, summer = 0 }",
+            "x = { default = 7, months = [{ from = 6, to = 8, days = 0 }] }",
+        ] {
+            let result = toml::from_str::<toml::Table>(interval).unwrap()["x"]
+                .clone()
+                .try_into::<Interval>();
+            assert!(result.is_err(), "{interval}");
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::config::WATER;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    /// The task was carried out.  Older state files call this `watered`.
+    #[serde(alias = "watered")]
+    Done,
+    /// Reminders for the task were postponed until the event's `until` time.
+    Snoozed,
+}
+
+impl std::fmt::Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventKind::Done => f.write_str("done"),
+            EventKind::Snoozed => f.write_str("snoozed"),
+        }
+    }
+}
+
+fn default_task() -> String {
+    WATER.to_string()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub at: NaiveDateTime,
+    #[serde(default = "default_task")]
+    pub task: String,
+    pub kind: EventKind,
+    /// End of a snooze.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Number of readings kept per plant and metric; older ones are dropped.
+pub const READINGS_LEN: usize = 100;
+
+/// A measurement from a sensor, e.g. of soil moisture.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reading {
+    pub at: NaiveDateTime,
+    pub metric: String,
+    pub value: f64,
+}
+
+/// On-disk representation of a [`PlantStatus`], which also accepts the old
+/// single-timestamp format.
+#[derive(Deserialize)]
+struct PlantStatusRepr {
+    #[serde(default)]
+    history: Vec<Event>,
+    #[serde(default)]
+    readings: Vec<Reading>,
+    last_watered: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "PlantStatusRepr")]
+pub struct PlantStatus {
+    /// Append-only log of events, oldest first.
+    pub history: Vec<Event>,
+    /// Sensor readings, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub readings: Vec<Reading>,
+}
+
+impl From<PlantStatusRepr> for PlantStatus {
+    fn from(repr: PlantStatusRepr) -> Self {
+        let mut status = PlantStatus {
+            history: repr.history,
+            readings: repr.readings,
+        };
+        // Old state files stored a sentinel date in 1900 for plants which had never been watered.
+        let sentinel = NaiveDate::from_ymd_opt(1900, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        if let Some(at) = repr.last_watered {
+            if status.history.is_empty() && at > sentinel {
+                status.record(at, WATER, EventKind::Done, None);
+            }
+        }
+        status
+    }
+}
+
+impl PlantStatus {
+    /// When `task` was last carried out, if ever.
+    pub fn last_done(&self, task: &str) -> Option<NaiveDateTime> {
+        self.history
+            .iter()
+            .rev()
+            .find(|e| e.kind == EventKind::Done && e.task == task)
+            .map(|e| e.at)
+    }
+
+    /// The end of the snooze on `task` in effect at `now`, if any.  Doing the task
+    /// cancels an earlier snooze.
+    pub fn snoozed_until(&self, task: &str, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let latest = self.history.iter().rev().find(|e| e.task == task)?;
+        match latest.until {
+            Some(until) if latest.kind == EventKind::Snoozed && until > now => Some(until),
+            _ => None,
+        }
+    }
+
+    /// Add an event to the history, keeping it sorted by time.
+    pub fn record(&mut self, at: NaiveDateTime, task: &str, kind: EventKind, note: Option<String>) {
+        self.insert(Event {
+            at,
+            task: task.to_string(),
+            kind,
+            until: None,
+            note,
+        });
+    }
+
+    /// Record a snooze of `task` lasting until `until`.
+    pub fn snooze(
+        &mut self,
+        at: NaiveDateTime,
+        task: &str,
+        until: NaiveDateTime,
+        note: Option<String>,
+    ) {
+        self.insert(Event {
+            at,
+            task: task.to_string(),
+            kind: EventKind::Snoozed,
+            until: Some(until),
+            note,
+        });
+    }
+
+    /// Add a reading, keeping readings sorted by time.  A reading of the same metric at
+    /// the same time is replaced.  Returns whether anything changed.
+    pub fn add_reading(&mut self, reading: Reading) -> bool {
+        let same = |r: &Reading| r.at == reading.at && r.metric == reading.metric;
+        if let Some(existing) = self.readings.iter_mut().find(|r| same(r)) {
+            let changed = existing.value != reading.value;
+            existing.value = reading.value;
+            return changed;
+        }
+        let idx = self.readings.partition_point(|r| r.at <= reading.at);
+        let metric = reading.metric.clone();
+        self.readings.insert(idx, reading);
+        let count = self.readings.iter().filter(|r| r.metric == metric).count();
+        if count > READINGS_LEN {
+            let oldest = self
+                .readings
+                .iter()
+                .position(|r| r.metric == metric)
+                .unwrap();
+            self.readings.remove(oldest);
+        }
+        true
+    }
+
+    /// The most recent reading of `metric`, if any.
+    pub fn latest_reading(&self, metric: &str) -> Option<&Reading> {
+        self.readings.iter().rev().find(|r| r.metric == metric)
+    }
+
+    fn insert(&mut self, event: Event) {
+        let idx = self.history.partition_point(|e| e.at <= event.at);
+        self.history.insert(idx, event);
+    }
+
+    /// Iterate over the history along with the time elapsed since the previous event
+    /// of the same kind for the same task.
+    pub fn history_with_intervals(&self) -> impl Iterator<Item = (&Event, Option<Duration>)> {
+        let mut prev: HashMap<(&str, EventKind), NaiveDateTime> = HashMap::new();
+        self.history.iter().map(move |e| {
+            let interval = prev.insert((&e.task, e.kind), e.at).map(|p| e.at - p);
+            (e, interval)
+        })
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct State {
+    pub plants: HashMap<String, PlantStatus>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_state_migrates() -> anyhow::Result<()> {
+        let state: State = toml::from_str(
+            r#"
+            [plants.fern]
+            last_watered = "2023-04-01T09:30:00"
+
+            [plants.cactus]
+            last_watered = "1900-01-01T00:00:00"
+            "#,
+        )?;
+        let fern = &state.plants["fern"];
+        assert_eq!(fern.history.len(), 1);
+        assert_eq!(
+            fern.last_done(WATER),
+            Some("2023-04-01T09:30:00".parse().unwrap())
+        );
+        assert_eq!(state.plants["cactus"].last_done(WATER), None);
+        Ok(())
+    }
+
+    #[test]
+    fn history_stays_sorted() {
+        let mut status = PlantStatus::default();
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        status.record(t("2023-04-10T00:00:00"), WATER, EventKind::Done, None);
+        status.record(t("2023-04-05T00:00:00"), "mist", EventKind::Done, None);
+        status.record(t("2023-04-01T00:00:00"), WATER, EventKind::Done, None);
+        assert_eq!(status.last_done(WATER), Some(t("2023-04-10T00:00:00")));
+        assert_eq!(status.last_done("mist"), Some(t("2023-04-05T00:00:00")));
+        let intervals: Vec<_> = status
+            .history_with_intervals()
+            .map(|(_, i)| i.map(|d| d.num_days()))
+            .collect();
+        assert_eq!(intervals, [None, None, Some(9)]);
+    }
+
+    #[test]
+    fn doing_a_task_cancels_its_snooze() {
+        let mut status = PlantStatus::default();
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        status.snooze(
+            t("2023-04-01T00:00:00"),
+            WATER,
+            t("2023-04-03T00:00:00"),
+            None,
+        );
+        assert_eq!(
+            status.snoozed_until(WATER, t("2023-04-02T00:00:00")),
+            Some(t("2023-04-03T00:00:00"))
+        );
+        assert_eq!(status.snoozed_until(WATER, t("2023-04-03T00:00:00")), None);
+        assert_eq!(status.snoozed_until("mist", t("2023-04-02T00:00:00")), None);
+        assert_eq!(status.last_done(WATER), None);
+        status.record(t("2023-04-01T12:00:00"), WATER, EventKind::Done, None);
+        assert_eq!(status.snoozed_until(WATER, t("2023-04-02T00:00:00")), None);
+    }
+
+    #[test]
+    fn readings_replace_and_trim() {
+        let mut status = PlantStatus::default();
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        let reading = |at: &str, value| Reading {
+            at: t(at),
+            metric: "moisture".to_string(),
+            value,
+        };
+        assert!(status.add_reading(reading("2023-04-02T00:00:00", 30.0)));
+        assert!(status.add_reading(reading("2023-04-01T00:00:00", 40.0)));
+        assert!(!status.add_reading(reading("2023-04-02T00:00:00", 30.0)));
+        assert!(status.add_reading(reading("2023-04-02T00:00:00", 25.0)));
+        assert_eq!(status.readings.len(), 2);
+        assert_eq!(status.latest_reading("moisture").unwrap().value, 25.0);
+        assert!(status.latest_reading("light").is_none());
+
+        let start = t("2023-05-01T00:00:00");
+        for i in 0..READINGS_LEN as i64 {
+            status.add_reading(Reading {
+                at: start + Duration::hours(i),
+                metric: "moisture".to_string(),
+                value: 50.0,
+            });
+        }
+        assert_eq!(status.readings.len(), READINGS_LEN);
+        assert_eq!(status.readings[0].at, start);
+    }
+}
@@ -0,0 +1,228 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use crate::adapt::median;
+use crate::config::Config;
+use crate::state::{EventKind, PlantStatus};
+
+/// Number of plants reported as most neglected.
+const MOST_NEGLECTED: usize = 3;
+
+/// How well a task was kept up with for one plant over a period.
+#[derive(Clone, Debug)]
+pub struct PlantStats {
+    pub plant: String,
+    pub task: String,
+    /// Times the task was done in the period.
+    pub done: usize,
+    /// Interval in days in effect at the end of the period.
+    pub interval: u64,
+    /// Days between each time the task was done in the period and the time before.
+    pub intervals: Vec<f64>,
+    /// Of those times, how many were on or before the due date, allowing for snoozes.
+    pub on_time: usize,
+    pub late: usize,
+    /// The most days the task went past its due date, including if it is still overdue
+    /// at the end of the period.
+    pub longest_overdue: i64,
+}
+
+impl PlantStats {
+    pub fn mean_interval(&self) -> Option<f64> {
+        let n = self.intervals.len();
+        (n > 0).then(|| self.intervals.iter().sum::<f64>() / n as f64)
+    }
+
+    pub fn median_interval(&self) -> Option<f64> {
+        median(&self.intervals)
+    }
+
+    pub fn on_time_percent(&self) -> Option<f64> {
+        percent(self.on_time, self.late)
+    }
+}
+
+fn percent(on_time: usize, late: usize) -> Option<f64> {
+    let n = on_time + late;
+    (n > 0).then(|| 100.0 * on_time as f64 / n as f64)
+}
+
+/// Figures for a plant's task from its history between `since` (or the beginning) and
+/// `until`.  Each time the task is done, it falls due again after the interval in effect
+/// that day, or when a later snooze ends.
+pub fn plant_stats(
+    config: &Config,
+    plant: &str,
+    task: &str,
+    status: &PlantStatus,
+    since: Option<NaiveDateTime>,
+    until: NaiveDateTime,
+) -> PlantStats {
+    let config_plant = &config.plants[plant];
+    let mut stats = PlantStats {
+        plant: plant.to_string(),
+        task: task.to_string(),
+        done: 0,
+        interval: config
+            .interval(config_plant, task, until.date())
+            .unwrap_or_default(),
+        intervals: Vec::new(),
+        on_time: 0,
+        late: 0,
+        longest_overdue: 0,
+    };
+    let mut last_done: Option<NaiveDateTime> = None;
+    let mut due: Option<NaiveDate> = None;
+    for event in status
+        .history
+        .iter()
+        .filter(|e| e.task == task && e.at <= until)
+    {
+        match event.kind {
+            EventKind::Snoozed => {
+                if let (Some(date), Some(until)) = (due, event.until) {
+                    due = Some(date.max(until.date()));
+                }
+            }
+            EventKind::Done => {
+                if since.is_none_or(|since| event.at >= since) {
+                    stats.done += 1;
+                    if let (Some(previous), Some(due)) = (last_done, due) {
+                        let interval = event.at - previous;
+                        stats
+                            .intervals
+                            .push(interval.num_minutes() as f64 / (24.0 * 60.0));
+                        let overdue = (event.at.date() - due).num_days();
+                        if overdue > 0 {
+                            stats.late += 1;
+                            stats.longest_overdue = stats.longest_overdue.max(overdue);
+                        } else {
+                            stats.on_time += 1;
+                        }
+                    }
+                }
+                let date = event.at.date();
+                last_done = Some(event.at);
+                due = config
+                    .interval(config_plant, task, date)
+                    .map(|days| date + Duration::days(days as i64));
+            }
+        }
+    }
+    if let Some(due) = due {
+        let overdue = (until.date() - due).num_days();
+        stats.longest_overdue = stats.longest_overdue.max(overdue);
+    }
+    stats
+}
+
+/// Figures for all plants together.
+#[derive(Clone, Debug)]
+pub struct Overall {
+    pub done: usize,
+    pub on_time: usize,
+    pub late: usize,
+    /// The plant whose task went longest past its due date, and by how many days.
+    pub longest_overdue: Option<(String, i64)>,
+    /// Plants which fell behind, worst first: those with nothing done at all, then the
+    /// smallest share done on time, then the longest overdue.
+    pub most_neglected: Vec<String>,
+}
+
+impl Overall {
+    pub fn new(stats: &[PlantStats]) -> Self {
+        let longest_overdue = stats
+            .iter()
+            .filter(|s| s.longest_overdue > 0)
+            .max_by_key(|s| s.longest_overdue)
+            .map(|s| (s.plant.clone(), s.longest_overdue));
+        let mut behind: Vec<&PlantStats> = stats
+            .iter()
+            .filter(|s| s.late > 0 || s.longest_overdue > 0)
+            .collect();
+        behind.sort_by(|a, b| {
+            // Below any share, as nothing was done on time.
+            let on_time = |s: &PlantStats| s.on_time_percent().unwrap_or(-1.0);
+            on_time(a)
+                .total_cmp(&on_time(b))
+                .then(b.longest_overdue.cmp(&a.longest_overdue))
+        });
+        Overall {
+            done: stats.iter().map(|s| s.done).sum(),
+            on_time: stats.iter().map(|s| s.on_time).sum(),
+            late: stats.iter().map(|s| s.late).sum(),
+            longest_overdue,
+            most_neglected: behind
+                .iter()
+                .take(MOST_NEGLECTED)
+                .map(|s| s.plant.clone())
+                .collect(),
+        }
+    }
+
+    pub fn on_time_percent(&self) -> Option<f64> {
+        percent(self.on_time, self.late)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WATER;
+
+    #[test]
+    fn keeping_up() -> anyhow::Result<()> {
+        let config: Config = toml::from_str(
+            r#"
+            [fern]
+            watering_interval = 7
+            [cactus]
+            watering_interval = 14
+            "#,
+        )?;
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        let mut fern = PlantStatus::default();
+        for at in [
+            "2023-03-01T08:00:00",
+            "2023-03-08T20:00:00",
+            "2023-03-18T08:00:00",
+            "2023-03-22T08:00:00",
+        ] {
+            fern.record(t(at), WATER, EventKind::Done, None);
+        }
+        // Snoozed until the 1st, so watering then is on time.
+        fern.snooze(
+            t("2023-03-28T08:00:00"),
+            WATER,
+            t("2023-04-01T08:00:00"),
+            None,
+        );
+        fern.record(t("2023-04-01T09:00:00"), WATER, EventKind::Done, None);
+        let mut cactus = PlantStatus::default();
+        cactus.record(t("2023-03-01T08:00:00"), WATER, EventKind::Done, None);
+
+        let until = t("2023-04-05T12:00:00");
+        let since = Some(t("2023-03-05T00:00:00"));
+        let stats = plant_stats(&config, "fern", WATER, &fern, since, until);
+        assert_eq!(stats.done, 4);
+        assert_eq!((stats.on_time, stats.late), (3, 1));
+        assert_eq!(stats.longest_overdue, 3);
+        assert_eq!(stats.on_time_percent(), Some(75.0));
+        assert_eq!(stats.median_interval(), Some(8.5));
+
+        let all = plant_stats(&config, "fern", WATER, &fern, None, until);
+        assert_eq!(all.done, 5);
+        assert_eq!(all.intervals.len(), 4);
+
+        // Due on the 15th and still not watered.
+        let behind = plant_stats(&config, "cactus", WATER, &cactus, since, until);
+        assert_eq!((behind.done, behind.on_time, behind.late), (0, 0, 0));
+        assert_eq!(behind.longest_overdue, 21);
+
+        let overall = Overall::new(&[stats, behind]);
+        assert_eq!(overall.done, 4);
+        assert_eq!(overall.on_time_percent(), Some(75.0));
+        assert_eq!(overall.longest_overdue, Some(("cactus".to_string(), 21)));
+        assert_eq!(overall.most_neglected, ["cactus", "fern"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime};
+
+/// Parse a duration such as `2d`, `12h`, `1w` or `90m`.  A bare number is taken as days.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (n, unit) = s.split_at(split);
+    let n: i64 = n
+        .parse()
+        .with_context(|| format!("invalid duration: {s:?}"))?;
+    let d = match unit.trim() {
+        "" | "d" | "day" | "days" => Duration::try_days(n),
+        "w" | "week" | "weeks" => Duration::try_weeks(n),
+        "h" | "hour" | "hours" => Duration::try_hours(n),
+        "m" | "min" | "mins" | "minutes" => Duration::try_minutes(n),
+        _ => bail!("invalid duration unit in {s:?}, expected one of w, d, h or m"),
+    };
+    d.ok_or_else(|| anyhow!("duration {s:?} is too long"))
+}
+
+/// Parse an absolute date (`2023-04-01`, meaning the start of that day) or date and time
+/// (`2023-04-01 08:00` or `2023-04-01T08:00:00`).
+pub fn parse_datetime(s: &str) -> Result<NaiveDateTime> {
+    let s = s.trim();
+    for fmt in [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, fmt) {
+            return Ok(t);
+        }
+    }
+    match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(d) => Ok(d.and_hms_opt(0, 0, 0).unwrap()),
+        Err(_) => bail!("invalid date {s:?}, expected YYYY-MM-DD or YYYY-MM-DD HH:MM"),
+    }
+}
+
+/// Parse a timestamp as programs write them: anything accepted by [`parse_datetime`], or
+/// RFC 3339, which is converted to local time.
+pub fn parse_timestamp(s: &str) -> Result<NaiveDateTime> {
+    parse_datetime(s).or_else(|_| {
+        DateTime::parse_from_rfc3339(s.trim())
+            .map(|t| t.with_timezone(&Local).naive_local())
+            .map_err(|_| anyhow!("invalid timestamp {s:?}"))
+    })
+}
+
+/// Parse a point in time relative to `now`: `now`, `today` or `yesterday` (optionally
+/// followed by a time, e.g. `yesterday 18:00`), `<duration> ago` (e.g. `3 days ago`,
+/// `2h ago`), or anything accepted by [`parse_datetime`].
+pub fn parse_when(s: &str, now: NaiveDateTime) -> Result<NaiveDateTime> {
+    let s = s.trim();
+    // Keywords are matched ignoring case, but dates keep theirs for the `T` separator.
+    let lower = s.to_ascii_lowercase();
+    if lower == "now" {
+        return Ok(now);
+    }
+    if let Some(duration) = lower.strip_suffix("ago") {
+        return now
+            .checked_sub_signed(parse_duration(duration)?)
+            .ok_or_else(|| anyhow!("{s:?} is too long ago"));
+    }
+    let (day, time) = lower.split_once(' ').unwrap_or((&lower, ""));
+    let date = match day {
+        "today" => now.date(),
+        "yesterday" => now.date() - Duration::days(1),
+        _ => return parse_datetime(s),
+    };
+    let time = match time.trim() {
+        "" => now.time(),
+        time => NaiveTime::parse_from_str(time, "%H:%M")
+            .with_context(|| format!("invalid time {time:?}, expected HH:MM"))?,
+    };
+    Ok(date.and_time(time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() -> Result<()> {
+        assert_eq!(parse_duration("2d")?, Duration::days(2));
+        assert_eq!(parse_duration("3")?, Duration::days(3));
+        assert_eq!(parse_duration("1w")?, Duration::days(7));
+        assert_eq!(parse_duration("12 hours")?, Duration::hours(12));
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("2y").is_err());
+        assert!(parse_duration("9999999999999999w").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn datetimes() -> Result<()> {
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        assert_eq!(parse_datetime("2023-04-01")?, t("2023-04-01T00:00:00"));
+        assert_eq!(
+            parse_datetime("2023-04-01 08:30")?,
+            t("2023-04-01T08:30:00")
+        );
+        assert_eq!(
+            parse_datetime("2023-04-01T08:30:15")?,
+            t("2023-04-01T08:30:15")
+        );
+        assert!(parse_datetime("tomorrow-ish").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn relative_times() -> Result<()> {
+        let t = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        let now = t("2023-04-10T12:30:00");
+        assert_eq!(parse_when("now", now)?, now);
+        assert_eq!(parse_when("yesterday", now)?, t("2023-04-09T12:30:00"));
+        assert_eq!(
+            parse_when("Yesterday 08:00", now)?,
+            t("2023-04-09T08:00:00")
+        );
+        assert_eq!(parse_when("today 07:15", now)?, t("2023-04-10T07:15:00"));
+        assert_eq!(parse_when("3 days ago", now)?, t("2023-04-07T12:30:00"));
+        assert_eq!(parse_when("2h ago", now)?, t("2023-04-10T10:30:00"));
+        assert_eq!(
+            parse_when("2023-04-01 08:00", now)?,
+            t("2023-04-01T08:00:00")
+        );
+        assert_eq!(
+            parse_when(" 2023-04-01T08:00 ", now)?,
+            t("2023-04-01T08:00:00")
+        );
+        assert!(parse_when("someday", now).is_err());
+        assert!(parse_when("99999999999d ago", now).is_err());
+        Ok(())
+    }
+}